@@ -1,5 +1,7 @@
 // src/cli.rs
-use clap::{Parser, Subcommand};
+use crate::i18n::Locale;
+use crate::output::OutputFormat;
+use clap::{ArgAction, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(name = "backupdbtool")]
@@ -14,6 +16,30 @@ pub struct Cli {
     /// Specific password for decryption config file
     #[arg(short, long)]
     pub password: Option<String>,
+
+    /// Output format of command results, logs are always written to stderr
+    #[arg(short, long, value_enum, global = true, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+
+    /// Language of tables, notifications and error messages (defaults to app.lang or LANG)
+    #[arg(long, value_enum, global = true)]
+    pub lang: Option<Locale>,
+
+    /// Increase log verbosity over logging.level (-v debug, -vv trace)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Decrease log verbosity under logging.level (-q warn, -qq error, -qqq off)
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    pub quiet: u8,
+}
+
+impl Cli {
+    /// -v 次数减去 -q 次数，超出 i8 范围时取边界值
+    pub fn verbosity(&self) -> i8 {
+        let verbosity = i16::from(self.verbose) - i16::from(self.quiet);
+        verbosity.clamp(i8::MIN.into(), i8::MAX.into()) as i8
+    }
 }
 
 #[derive(Subcommand)]
@@ -44,6 +70,14 @@ pub enum Commands {
         /// Delete all yesterday before files
         #[arg(short, long, default_value_t = false)]
         all: bool,
+
+        /// Only print the files that would be deleted
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
     },
 
     /// Encrypt a TOML configuration file
@@ -58,8 +92,52 @@ pub enum Commands {
     },
 
     /// List available backups
-    List,
+    List {
+        /// Only list backups of this database
+        #[arg(short, long)]
+        database: Option<String>,
+
+        /// Only list backups taken at or after this time (YYYY-MM-DD[ HH:MM:SS], UTC)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only list backups taken at or before this time (YYYY-MM-DD[ HH:MM:SS], UTC)
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only list the newest N backups
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
+
+    /// Send the email digest of notification events in the last 24 hours
+    Digest,
+
+    /// Serve Prometheus metrics over HTTP until stopped
+    Metrics {
+        /// Address to listen on (defaults to metrics.listen)
+        #[arg(short, long)]
+        listen: Option<String>,
+    },
 
     /// Show tool version
     Version,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_saturates() {
+        let verbosity = |flag: String| {
+            Cli::try_parse_from(["backupdbtool", flag.as_str(), "version"])
+                .unwrap()
+                .verbosity()
+        };
+        assert_eq!(verbosity("-vv".to_string()), 2);
+        assert_eq!(verbosity("-qqq".to_string()), -3);
+        assert_eq!(verbosity(format!("-{}", "v".repeat(200))), i8::MAX);
+        assert_eq!(verbosity(format!("-{}", "q".repeat(200))), i8::MIN);
+    }
+}
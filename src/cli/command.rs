@@ -1,31 +1,67 @@
-use crate::config::{AllConfig, AppConfig};
+use crate::config::{self, AllConfig, AppConfig};
 use crate::crypt::aes::{
     EncryptedPackage, decrypt_data, encrypt_data, generate_key_from_password, generate_salt,
 };
 use crate::database::Database;
 use crate::error::{Error, Result};
-use crate::notify::Notify;
-use crate::notify::webhook::{WebHookNotify, WebHookSendData};
-use crate::storage::Storage;
-use crate::{compression, utils};
+use crate::i18n::{Msg, t, tf};
+use crate::notify::{Event, EventStatus, Job, Notify, Stage};
+use crate::output::{self, BackupResult, FileOutcome, OutcomeStatus, OutputFormat};
+use crate::storage::{CosItem, Storage};
+use crate::{compression, logging, metrics, utils};
 use std::fs;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::Instant;
+use tracing::{Instrument, Span, error, field, info, info_span, warn};
 
+#[tracing::instrument(name = "backup", skip_all, fields(database = database_name))]
 pub async fn backup_database(
     db: &dyn Database,
     database_name: &str,
     back_dir: &Path,
     password: &str,
-    notify: Option<WebHookNotify>,
+    notify: Option<Box<dyn Notify>>,
+    output: OutputFormat,
 ) -> Result<()> {
+    let notify = notify.as_deref();
+    let started = Instant::now();
+    let failed = |stage: Stage, e: Error| {
+        let database = database_name.to_string();
+        metrics::record(move |state| state.backup_failed(&database));
+        let event = Event::failure(Job::Backup, stage, Some(database_name), &e)
+            .with_duration(started.elapsed());
+        notify_failure(notify, event, e)
+    };
+
     // 1. 备份数据库
-    let backup_file = db.backup(database_name, back_dir).await?;
+    let span = info_span!("dump", database = database_name, bytes = field::Empty);
+    let backup_file = match db
+        .backup(database_name, back_dir)
+        .instrument(span.clone())
+        .await
+    {
+        Ok(file) => file,
+        Err(e) => return Err(failed(Stage::Dump, e).await),
+    };
+    record_size(&span, &backup_file).await;
     info!("Database backup created: {:?}", backup_file);
 
-    // 2. 压缩并加密
-    let compressed_file = compression::compress_and_encrypt(&backup_file, password).await?;
+    // 2. 压缩并加密，7z 在压缩的同时完成加密
+    let span = info_span!(
+        "compress_encrypt",
+        database = database_name,
+        bytes = field::Empty
+    );
+    let compressed_file = match compression::compress_and_encrypt(&backup_file, password)
+        .instrument(span.clone())
+        .await
+    {
+        Ok(file) => file,
+        Err(e) => return Err(failed(Stage::Compress, e).await),
+    };
+    record_size(&span, &compressed_file).await;
     info!("Backup compressed: {:?}", compressed_file);
 
     // 3. 删除原始SQL文件
@@ -33,64 +69,141 @@ pub async fn backup_database(
         error!("Failed to remove temporary SQL file: {}", e);
     }
 
-    if let Some(notify) = notify {
-        let message = format!("数据库 {} 备份成功", database_name);
-        let data = WebHookSendData::new("备份进度", message);
-        notify
-            .send(data)
-            .await
-            .map_err(|e| Error::Notification(e.to_string()))?;
+    let size = tokio::fs::metadata(&compressed_file).await?.len();
+    let (database, duration) = (database_name.to_string(), started.elapsed());
+    metrics::record(move |state| state.backup_succeeded(&database, duration, size));
+    if let Some(notify) = notify.filter(|notify| notify.accepts(EventStatus::Success)) {
+        let message = tf(Msg::BackupSucceeded, &[("database", &database_name)]);
+        let archive_key = compressed_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let checksum = utils::file_md5(&compressed_file).await.ok();
+        let event = Event::success(Job::Backup, message)
+            .with_database(database_name)
+            .with_duration(started.elapsed())
+            .with_archive(archive_key, size, checksum);
+        notify.send(&event).await?;
     }
 
     info!(
         "Backup completed successfully for database: {}",
         database_name
     );
-    Ok(())
+    let result = BackupResult {
+        database: database_name.to_string(),
+        size,
+        file: compressed_file,
+    };
+    output::print_rows(output, t(Msg::TitleBackupResult), &[result])
 }
 
+#[tracing::instrument(name = "upload", skip_all, fields(backend = config.cos_provider.as_str()))]
 pub async fn upload_to_cos(
     file: Option<String>,
     all: bool,
     config: &AppConfig,
     storage: Arc<dyn Storage>,
-    notify: Option<WebHookNotify>,
+    notify: Option<Box<dyn Notify>>,
+    output: OutputFormat,
 ) -> Result<()> {
+    let notify = notify.as_deref();
+    let started = Instant::now();
+    let result = upload(file, all, config, storage, notify, started, output).await;
+    let success = result.is_ok();
+    metrics::record(move |state| state.upload_finished(success));
+    match result {
+        Err(e) => {
+            let event = Event::failure(Job::Upload, Stage::Upload, None, &e)
+                .with_duration(started.elapsed());
+            Err(notify_failure(notify, event, e).await)
+        }
+        result => result,
+    }
+}
+
+async fn upload(
+    file: Option<String>,
+    all: bool,
+    config: &AppConfig,
+    storage: Arc<dyn Storage>,
+    notify: Option<&dyn Notify>,
+    started: Instant,
+    output: OutputFormat,
+) -> Result<()> {
+    let notify = notify.filter(|notify| notify.accepts(EventStatus::Success));
     if let Some(file_path) = file {
         // 上传单个文件
         let path = PathBuf::from(&file_path);
         if !path.exists() {
             return Err(Error::FileNotFound(path));
         }
-        storage
+        if let Err(e) = storage
             .upload(&path, &config.cos_path)
+            .instrument(utils::upload_span(&path))
             .await
-            .map_err(|_| Error::StorageUpload {
-                path,
-                message: "upload failed".to_string(),
-            })?;
+        {
+            metrics::record(|state| state.upload_files(0, 0, 1));
+            return Err(e);
+        }
+        metrics::record(|state| state.upload_files(1, 0, 0));
         if let Some(notify) = notify {
-            let message = format!("{} 上传成功", file_path);
-            let data = WebHookSendData::new("备份进度", message);
-            notify
-                .send(data)
-                .await
-                .map_err(|e| Error::Notification(e.to_string()))?;
+            let message = tf(Msg::FileUploaded, &[("file", &file_path)]);
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let size = tokio::fs::metadata(&path).await?.len();
+            let checksum = utils::file_md5(&path).await.ok();
+            let mut event = Event::success(Job::Upload, message)
+                .with_duration(started.elapsed())
+                .with_archive(
+                    utils::object_key(&config.cos_path, &file_name),
+                    size,
+                    checksum,
+                );
+            event.database = utils::parse_backup_name(&file_name).map(|name| name.database);
+            notify.send(&event).await?;
         }
         info!("File uploaded successfully: {}", file_path);
+        let outcome = FileOutcome::new(file_path, OutcomeStatus::Uploaded);
+        output::print_rows(output, t(Msg::TitleUploadResult), &[outcome])?;
     } else if all {
         // 上传所有备份文件
-        utils::upload_all_backups(&config.get_backup_dir(), storage, &config.cos_path)
-            .await
-            .map_err(|_| Error::Storage("upload failed".to_string()))?;
+        let report = utils::upload_all_backups(
+            &config.get_backup_dir(),
+            storage,
+            &config.cos_path,
+            config.upload_concurrency,
+            config.skip_uploaded,
+        )
+        .await?;
+        let counts = (
+            report.uploaded.len(),
+            report.skipped.len(),
+            report.failed.len(),
+        );
+        metrics::record(move |state| state.upload_files(counts.0, counts.1, counts.2));
+        if let Some(after_upload) = &config.after_upload {
+            let pruned =
+                utils::prune_uploaded_backups(&config.get_backup_dir(), &report, after_upload)
+                    .await?;
+            info!("Pruned {} uploaded local backups", pruned.len());
+        }
+        let outcomes: Vec<FileOutcome> = (&report).into();
+        output::print_rows(output, t(Msg::TitleUploadResult), &outcomes)?;
+        // 部分文件失败时由外层发送失败通知，汇总信息作为错误内容
+        if !report.is_success() {
+            return Err(Error::Storage(report.summary()));
+        }
         if let Some(notify) = notify {
-            let data = WebHookSendData::new("备份进度", "所有备份文件上传成功");
-            notify
-                .send(data)
-                .await
-                .map_err(|e| Error::Notification(e.to_string()))?;
+            let event =
+                Event::success(Job::Upload, report.summary()).with_duration(started.elapsed());
+            notify.send(&event).await?;
         }
-        info!("All backups uploaded successfully");
+        info!(
+            "All backups uploaded successfully: {} uploaded, {} skipped",
+            report.uploaded.len(),
+            report.skipped.len()
+        );
     } else {
         return Err(Error::CommandExecution(
             "Please specify either --file or --all flag".to_string(),
@@ -100,38 +213,142 @@ pub async fn upload_to_cos(
     Ok(())
 }
 
-pub async fn delete_from_cos(key: Option<String>, all: bool, storage: &dyn Storage,prefix:&str) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "delete", skip_all, fields(backend = config.cos_provider.as_str()))]
+pub async fn delete_from_cos(
+    key: Option<String>,
+    all: bool,
+    dry_run: bool,
+    yes: bool,
+    storage: &dyn Storage,
+    config: &AppConfig,
+    notify: Option<Box<dyn Notify>>,
+    output: OutputFormat,
+) -> Result<()> {
+    let started = Instant::now();
+    match delete(key, all, dry_run, yes, storage, config, output).await {
+        Err(e) => {
+            let event = Event::failure(Job::Delete, Stage::Delete, None, &e)
+                .with_duration(started.elapsed());
+            Err(notify_failure(notify.as_deref(), event, e).await)
+        }
+        result => result,
+    }
+}
+
+async fn delete(
+    key: Option<String>,
+    all: bool,
+    dry_run: bool,
+    yes: bool,
+    storage: &dyn Storage,
+    config: &AppConfig,
+    output: OutputFormat,
+) -> Result<()> {
     if let Some(key_str) = key {
-        storage
-            .delete(&key_str)
-            .await
-            .map_err(|e| Error::StorageDelete {
+        if dry_run {
+            let outcome = FileOutcome::new(key_str, OutcomeStatus::Pending);
+            return output::print_rows(output, t(Msg::TitleDeleteResult), &[outcome]);
+        }
+        // 指定 key 删除是明确的操作，不需要确认
+        storage.delete(&key_str).await.map_err(|e| match e {
+            Error::ObjectLocked { .. } => e,
+            e => Error::StorageDelete {
                 key: key_str.clone(),
                 message: e.to_string(),
-            })?;
+            },
+        })?;
         info!("File deleted successfully: {}", key_str);
+        let outcome = FileOutcome::new(key_str, OutcomeStatus::Deleted);
+        output::print_rows(output, t(Msg::TitleDeleteResult), &[outcome])?;
     } else if all {
         let files = storage
-            .list(prefix)
+            .list(&config.cos_path)
             .await
             .map_err(|e| Error::StorageList(e.to_string()))?;
+        let listed = files.clone();
+
+        // 每个数据库至少保留 min_keep_backups 个最新备份，避免备份连续失败时删光
+        let yesterday_files = utils::expired_remote_backups(files, config.min_keep_backups);
+        let local_files =
+            utils::expired_local_backups(&config.get_backup_dir(), config.min_keep_backups)?;
 
-        let yesterday_files: Vec<_> = files
-            .clone()
-            .into_iter()
-            .filter(|item| utils::is_yesterday_before(item.last_modified) && item.size > 0)
+        if yesterday_files.is_empty() && local_files.is_empty() {
+            info!("No backups to delete");
+            return output::print_rows::<FileOutcome>(output, t(Msg::TitleDeleteResult), &[]);
+        }
+        if dry_run {
+            let outcomes: Vec<FileOutcome> = yesterday_files
+                .iter()
+                .map(|entry| entry.key.clone())
+                .chain(local_files.iter().map(|path| path.display().to_string()))
+                .map(|file| FileOutcome::new(file, OutcomeStatus::Pending))
+                .collect();
+            return output::print_rows(output, t(Msg::TitleDeleteResult), &outcomes);
+        }
+        // 计划写到 stderr，stdout 只输出结果
+        eprintln!("=== {} ===", t(Msg::TitleRemotePlan));
+        for entry in &yesterday_files {
+            eprintln!("{}", entry.key);
+        }
+        eprintln!("=== {} ===", t(Msg::TitleLocalPlan));
+        for path in &local_files {
+            eprintln!("{}", path.display());
+        }
+        let prompt = tf(
+            Msg::ConfirmDeleteAll,
+            &[
+                ("remote", &yesterday_files.len()),
+                ("local", &local_files.len()),
+            ],
+        );
+        if !yes {
+            confirm(&prompt)?;
+        }
+
+        utils::cleanup_old_backups(&local_files).await?;
+        let mut outcomes: Vec<FileOutcome> = local_files
+            .iter()
+            .map(|path| FileOutcome::new(path.display().to_string(), OutcomeStatus::Deleted))
             .collect();
+
+        let mut locked = Vec::new();
+        let mut deleted = Vec::new();
         for entry in yesterday_files {
-            storage
-                .delete(&entry.key)
-                .await
-                .map_err(|_| Error::StorageDelete {
-                    key: entry.key,
-                    message: "delete failed".to_string(),
-                })?;
+            match storage.delete(&entry.key).await {
+                Ok(()) => {
+                    deleted.push(entry.key.clone());
+                    outcomes.push(FileOutcome::new(entry.key, OutcomeStatus::Deleted));
+                }
+                // 仍处于保留期的对象跳过，最后统一汇报
+                Err(Error::ObjectLocked { key, message }) => {
+                    warn!("Skip locked object {}: {}", key, message);
+                    outcomes.push(FileOutcome::new(key.clone(), OutcomeStatus::Locked));
+                    locked.push(key);
+                }
+                Err(e) => {
+                    outcomes.push(FileOutcome::failed(entry.key.clone(), e.to_string()));
+                    record_deleted(listed, &deleted);
+                    output::print_rows(output, t(Msg::TitleDeleteResult), &outcomes)?;
+                    return Err(Error::StorageDelete {
+                        key: entry.key,
+                        message: "delete failed".to_string(),
+                    });
+                }
+            }
         }
+        record_deleted(listed, &deleted);
+        output::print_rows(output, t(Msg::TitleDeleteResult), &outcomes)?;
 
-        info!("yesterday before backups delete successfully");
+        if locked.is_empty() {
+            info!("yesterday before backups delete successfully");
+        } else {
+            warn!(
+                "yesterday before backups deleted, {} locked objects kept: {}",
+                locked.len(),
+                locked.join(", ")
+            );
+        }
     } else {
         return Err(Error::CommandExecution(
             "Please specify either --key or --all flag".to_string(),
@@ -141,6 +358,120 @@ pub async fn delete_from_cos(key: Option<String>, all: bool, storage: &dyn Stora
     Ok(())
 }
 
+/// 记录删除数量，并用删除后剩余的远端备份更新远端统计
+fn record_deleted(listed: Vec<CosItem>, deleted: &[String]) {
+    let remaining: Vec<CosItem> = listed
+        .into_iter()
+        .filter(|item| !deleted.contains(&item.key))
+        .collect();
+    let remote = utils::summarize_backups(&remaining);
+    let count = deleted.len();
+    metrics::record(move |state| {
+        state.deleted(count);
+        state.remote_backups(&remote);
+    });
+}
+
+pub async fn serve_metrics(listen: Option<String>, config: &AllConfig) -> Result<()> {
+    let metrics_config = config.metrics.as_ref();
+    let listen = listen
+        .or_else(|| metrics_config.map(|metrics| metrics.listen.clone()))
+        .unwrap_or_else(config::default_metrics_listen);
+    let state_file = metrics_config
+        .and_then(|metrics| metrics.state_file.clone())
+        .unwrap_or_else(|| config.app.metrics_state_path());
+    metrics::serve(&listen, state_file).await
+}
+
+pub async fn send_digest(config: &AllConfig) -> Result<()> {
+    for email in config::get_email_digests(config)? {
+        email.send_digest().await?;
+    }
+    Ok(())
+}
+
+/// 阶段完成后把产物大小记录到 span 上
+async fn record_size(span: &Span, path: &Path) {
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        span.record("bytes", metadata.len());
+    }
+}
+
+/// 发送失败通知并原样返回错误，通知本身失败只记录日志，不覆盖原始错误
+async fn notify_failure(notify: Option<&dyn Notify>, event: Event, error: Error) -> Error {
+    let Some(notify) = notify.filter(|notify| notify.accepts(EventStatus::Failure)) else {
+        return error;
+    };
+    // 成功通知发送失败时不再重复通知
+    if matches!(error, Error::Notification(_)) {
+        return error;
+    }
+
+    let event = event.with_logs(logging::failure_log());
+    if let Err(e) = notify.send(&event).await {
+        error!("Failed to send failure notification: {}", e);
+    }
+    error
+}
+
+/// 在终端询问用户是否继续。拒绝或 stdin 不是终端时返回错误，
+/// 避免定时任务漏加 --yes 时静默跳过删除却以 0 退出
+fn confirm(prompt: &str) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        return Err(Error::CommandExecution(
+            "stdin is not a terminal, pass --yes to delete without confirmation".to_string(),
+        ));
+    }
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if matches!(answer.trim(), "y" | "Y" | "yes" | "YES") {
+        Ok(())
+    } else {
+        Err(Error::Cancelled)
+    }
+}
+
+pub async fn list_backups(
+    database: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<usize>,
+    storage: &dyn Storage,
+    config: &AppConfig,
+    output: OutputFormat,
+) -> Result<()> {
+    let filter = utils::ListFilter {
+        database,
+        since: since
+            .map(|value| utils::parse_datetime(&value, false))
+            .transpose()?,
+        until: until
+            .map(|value| utils::parse_datetime(&value, true))
+            .transpose()?,
+        limit,
+    };
+
+    let files = storage.list(&config.cos_path).await?;
+    let remote = utils::summarize_backups(&files);
+    metrics::record(move |state| state.remote_backups(&remote));
+    let files = utils::filter_backups(files, &filter);
+    let summaries = utils::summarize_backups(&files);
+    match output {
+        OutputFormat::Json => output::print_json(&serde_json::json!({
+            "backups": files,
+            "summary": summaries,
+        })),
+        // csv 只能表示一张表，输出文件列表
+        OutputFormat::Csv => output::print_rows(output, t(Msg::TitleFileList), &files),
+        OutputFormat::Table => {
+            output::print_rows(output, t(Msg::TitleFileList), &files)?;
+            output::print_rows(output, t(Msg::TitleSummary), &summaries)
+        }
+    }
+}
+
 pub fn encrypt_yaml_file(source: &PathBuf, destination: &PathBuf, password: &str) -> Result<()> {
     // Read the source yaml file
     let toml_content = fs::read_to_string(source).map_err(Error::Io)?;
@@ -197,3 +528,152 @@ pub fn decrypt_yaml_file(encrypted_file: &PathBuf, password: &str) -> Result<All
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CosProvider, DbType, NotifyOn};
+    use crate::notify::Dispatcher;
+    use crate::notify::tests::channel;
+    use std::sync::Mutex;
+    use tempfile::tempdir;
+
+    struct FailingDb;
+
+    #[async_trait::async_trait]
+    impl Database for FailingDb {
+        async fn backup(&self, _database_name: &str, _backup_dir: &Path) -> Result<PathBuf> {
+            Err(Error::DatabaseBackup("pg_dump exited with 1".to_string()))
+        }
+    }
+
+    /// fail 为 true 时上传、删除都返回错误
+    struct MockStorage {
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Storage for MockStorage {
+        async fn upload(&self, _file_path: &Path, _cos_path: &str) -> Result<()> {
+            if self.fail {
+                return Err(Error::Storage("HTTP 500".to_string()));
+            }
+            Ok(())
+        }
+        async fn list(&self, _key: &str) -> Result<Vec<CosItem>> {
+            Ok(Vec::new())
+        }
+        async fn delete(&self, _backup_name: &str) -> Result<()> {
+            if self.fail {
+                return Err(Error::Storage("HTTP 500".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    fn app_config(backup_dir: &Path) -> AppConfig {
+        AppConfig {
+            backup_dir: backup_dir.to_path_buf(),
+            db_type: DbType::Postgresql,
+            cos_provider: CosProvider::TencentCos,
+            cos_path: "db/".to_string(),
+            compress_password: "password".to_string(),
+            upload_concurrency: 1,
+            skip_uploaded: false,
+            after_upload: None,
+            min_keep_backups: 3,
+            lang: None,
+        }
+    }
+
+    fn recorder(notify_on: NotifyOn, fail: bool) -> (Box<dyn Notify>, Arc<Mutex<Vec<String>>>) {
+        let (channel, events) = channel("test", notify_on, fail);
+        (Box::new(Dispatcher::new(vec![channel])), events)
+    }
+
+    async fn upload_file(fail: bool, notify: Box<dyn Notify>) -> Result<()> {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("orders_20241001_020000.7z");
+        fs::write(&file, b"backup").unwrap();
+        upload_to_cos(
+            Some(file.display().to_string()),
+            false,
+            &app_config(dir.path()),
+            Arc::new(MockStorage { fail }),
+            Some(notify),
+            OutputFormat::Json,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_notify_on_failure_skips_success() {
+        let (notify, events) = recorder(NotifyOn::Failure, false);
+        upload_file(false, notify).await.unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        let (notify, events) = recorder(NotifyOn::Always, false);
+        upload_file(false, notify).await.unwrap();
+        assert_eq!(*events.lock().unwrap(), ["upload.succeeded"]);
+    }
+
+    #[tokio::test]
+    async fn test_success_notification_failure_not_reported_twice() {
+        let (notify, events) = recorder(NotifyOn::Always, true);
+        let result = upload_file(false, notify).await;
+        assert!(matches!(result, Err(Error::Notification(_))));
+        assert_eq!(*events.lock().unwrap(), ["upload.succeeded"]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_failure_notifies_once() {
+        let dir = tempdir().unwrap();
+        let (notify, events) = recorder(NotifyOn::Failure, false);
+        let result = backup_database(
+            &FailingDb,
+            "orders",
+            dir.path(),
+            "password",
+            Some(notify),
+            OutputFormat::Json,
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::DatabaseBackup(message)) if message.contains("exited with 1"))
+        );
+        assert_eq!(*events.lock().unwrap(), ["backup.failed"]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_failure_notifies_once() {
+        // 通知渠道本身出错时仍返回原始的上传错误
+        for notify_fail in [false, true] {
+            let (notify, events) = recorder(NotifyOn::Failure, notify_fail);
+            let result = upload_file(true, notify).await;
+            assert!(matches!(result, Err(Error::Storage(message)) if message == "HTTP 500"));
+            assert_eq!(*events.lock().unwrap(), ["upload.failed"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_failure_notifies_once() {
+        let dir = tempdir().unwrap();
+        let (notify, events) = recorder(NotifyOn::Failure, false);
+        let result = delete_from_cos(
+            Some("db/orders_20241001_020000.7z".to_string()),
+            false,
+            false,
+            false,
+            &MockStorage { fail: true },
+            &app_config(dir.path()),
+            Some(notify),
+            OutputFormat::Json,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::StorageDelete { key, .. }) if key == "db/orders_20241001_020000.7z"
+        ));
+        assert_eq!(*events.lock().unwrap(), ["delete.failed"]);
+    }
+}
@@ -2,14 +2,27 @@ use crate::cli::command::decrypt_yaml_file;
 use crate::database::Database;
 use crate::database::{mysql::MySql, postgresql::PostgreSql};
 use crate::error::{Error, Result};
+use crate::i18n::Locale;
+use crate::notify::dingtalk::DingTalkNotify;
+use crate::notify::email::EmailNotify;
+use crate::notify::feishu::FeishuNotify;
+use crate::notify::slack::SlackNotify;
+use crate::notify::telegram::TelegramNotify;
 use crate::notify::webhook::WebHookNotify;
+use crate::notify::wecom::WeComNotify;
+use crate::notify::{Channel, Dispatcher, Job, Notify};
 use crate::storage::Storage;
 use crate::storage::aliyun_oss::AliyunOss;
+use crate::storage::gcs::Gcs;
 use crate::storage::local_storage::LocalStorage;
+use crate::storage::retry::RetryStorage;
 use crate::storage::s3_compatible::S3Oss;
 use crate::storage::tencent_cos::TencentCos;
+use crate::storage::throttle;
+use crate::storage::webdav::WebDav;
 use config::{Config, File};
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::fs::create_dir_all;
@@ -22,7 +35,24 @@ pub struct AllConfig {
     pub mysql: MySqlConfig,
     pub aliyun_oss: AliyunOssConfig,
     pub s3: S3OssConfig,
+    pub webdav: Option<WebDavConfig>,
+    pub gcs: Option<GcsConfig>,
+    pub local: Option<LocalStorageConfig>,
+    pub retry: Option<RetryConfig>,
+    pub bandwidth: Option<BandwidthConfig>,
     pub webhook: Option<WebHookConfig>,
+    pub notifier: Option<NotifierConfig>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    /// 所有渠道共用的通知模板，key 为事件类型
+    #[serde(default)]
+    pub templates: HashMap<String, MessageTemplate>,
+    #[serde(default)]
+    pub healthchecks: Vec<HealthcheckConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub otel: Option<OtelConfig>,
+    #[serde(default)]
+    pub logging: LoggingConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -32,6 +62,48 @@ pub struct AppConfig {
     pub cos_provider: CosProvider,
     pub cos_path: String,
     pub compress_password: String,
+    #[serde(default = "default_upload_concurrency")]
+    pub upload_concurrency: usize,
+    #[serde(default = "default_skip_uploaded")]
+    pub skip_uploaded: bool,
+    pub after_upload: Option<AfterUploadConfig>,
+    #[serde(default = "default_min_keep_backups")]
+    pub min_keep_backups: usize,
+    pub lang: Option<Locale>,
+}
+
+fn default_upload_concurrency() -> usize {
+    4
+}
+
+fn default_skip_uploaded() -> bool {
+    true
+}
+
+fn default_min_keep_backups() -> usize {
+    3
+}
+
+/// 上传确认后如何处理本地备份文件，最新的 keep_last 个始终保留在本地
+#[derive(Debug, Deserialize, Clone)]
+pub struct AfterUploadConfig {
+    pub action: AfterUploadAction,
+    pub move_to: Option<String>,
+    #[serde(default = "default_keep_last")]
+    pub keep_last: usize,
+}
+
+/// 默认保留最新的一个，避免刚上传的备份在本地被立即清理
+fn default_keep_last() -> usize {
+    1
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum AfterUploadAction {
+    #[serde(rename = "delete")]
+    Delete,
+    #[serde(rename = "move")]
+    Move,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -40,6 +112,10 @@ pub struct TencentCosConfig {
     pub secret_key: String,
     pub region: String,
     pub bucket: String,
+    /// list 最多返回的对象数，不设置时分页列出全部
+    pub max_keys: Option<u32>,
+    #[serde(default)]
+    pub object_options: ObjectOptions,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -48,6 +124,10 @@ pub struct AliyunOssConfig {
     pub secret_key: String,
     pub end_point: String,
     pub bucket: String,
+    /// list 最多返回的对象数，不设置时分页列出全部
+    pub max_keys: Option<u32>,
+    #[serde(default)]
+    pub object_options: ObjectOptions,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -57,6 +137,160 @@ pub struct S3OssConfig {
     pub end_point: Option<String>,
     pub bucket: String,
     pub region: Option<String>,
+    /// list 最多返回的对象数，不设置时分页列出全部
+    pub max_keys: Option<u32>,
+    #[serde(default)]
+    pub object_options: ObjectOptions,
+    pub object_lock: Option<ObjectLockConfig>,
+}
+
+/// S3 Object Lock 保留策略，存储桶需在创建时开启对象锁定
+#[derive(Debug, Deserialize, Clone)]
+pub struct ObjectLockConfig {
+    pub mode: ObjectLockMode,
+    pub retain_days: u32,
+    #[serde(default)]
+    pub legal_hold: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub enum ObjectLockMode {
+    #[serde(rename = "governance")]
+    Governance,
+    #[serde(rename = "compliance")]
+    Compliance,
+}
+
+/// 上传对象时附加的存储类型、服务端加密、元数据和标签
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ObjectOptions {
+    pub storage_class: Option<String>,
+    pub server_side_encryption: Option<String>,
+    pub kms_key_id: Option<String>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+/// 上传带宽限制（字节/秒），schedule 中命中的时间段优先于默认值
+#[derive(Debug, Deserialize, Clone)]
+pub struct BandwidthConfig {
+    pub rate_limit: Option<u64>,
+    #[serde(default)]
+    pub schedule: Vec<BandwidthWindow>,
+}
+
+/// 按本地时间 HH:MM 划分的限速时段，rate_limit 为空表示该时段不限速
+#[derive(Debug, Deserialize, Clone)]
+pub struct BandwidthWindow {
+    pub start: String,
+    pub end: String,
+    pub rate_limit: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub auth: WebDavAuth,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+pub enum WebDavAuth {
+    #[default]
+    #[serde(rename = "basic")]
+    Basic,
+    #[serde(rename = "digest")]
+    Digest,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GcsConfig {
+    pub bucket: String,
+    pub credentials_file: Option<String>,
+    #[serde(default = "default_gcs_endpoint")]
+    pub endpoint: String,
+    pub chunk_size: Option<usize>,
+    /// list 最多返回的对象数，不设置时分页列出全部
+    pub max_keys: Option<u32>,
+}
+
+fn default_gcs_endpoint() -> String {
+    "https://storage.googleapis.com".into()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LocalStorageConfig {
+    pub path: String,
+}
+
+/// 存储操作的重试策略，对所有存储后端生效
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetryConfig {
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// 每次尝试整个存储操作的时限，大文件上传需要覆盖限速下的完整传输时间，上传超时不重试
+    pub timeout_secs: Option<u64>,
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<RetryableError>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum RetryableError {
+    #[serde(rename = "timeout")]
+    Timeout,
+    #[serde(rename = "io")]
+    Io,
+    #[serde(rename = "upload")]
+    Upload,
+    #[serde(rename = "list")]
+    List,
+    #[serde(rename = "delete")]
+    Delete,
+    #[serde(rename = "storage")]
+    Storage,
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    30_000
+}
+
+fn default_retry_on() -> Vec<RetryableError> {
+    vec![
+        RetryableError::Timeout,
+        RetryableError::Io,
+        RetryableError::Upload,
+        RetryableError::List,
+        RetryableError::Delete,
+        RetryableError::Storage,
+    ]
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            attempts: default_retry_attempts(),
+            base_delay_ms: default_retry_base_delay_ms(),
+            max_delay_ms: default_retry_max_delay_ms(),
+            timeout_secs: None,
+            retry_on: default_retry_on(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -79,6 +313,411 @@ pub struct MySqlConfig {
 pub struct WebHookConfig {
     pub url: String,
     pub token: Option<String>,
+    #[serde(default)]
+    pub notify_on: NotifyOn,
+    #[serde(default)]
+    pub format: WebHookFormat,
+    pub signing: Option<WebHookSigning>,
+    #[serde(default)]
+    pub retry: WebHookRetryConfig,
+}
+
+/// Webhook 请求签名：对 `{timestamp}.{body}` 做 HmacSHA256，
+/// 签名以 `sha256=<hex>` 放在 signature_header，秒级时间戳放在 timestamp_header
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebHookSigning {
+    pub secret: String,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+}
+
+fn default_signature_header() -> String {
+    "X-Signature-256".into()
+}
+
+fn default_timestamp_header() -> String {
+    "X-Timestamp".into()
+}
+
+/// Webhook 发送失败（网络错误、5xx、408、429）时的重试策略，attempts 为 1 时不重试
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebHookRetryConfig {
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for WebHookRetryConfig {
+    fn default() -> Self {
+        WebHookRetryConfig {
+            attempts: default_retry_attempts(),
+            base_delay_ms: default_retry_base_delay_ms(),
+            max_delay_ms: default_retry_max_delay_ms(),
+        }
+    }
+}
+
+/// 通知渠道，type 选择平台，其余字段为该平台的配置。
+/// events 和 databases 为空时不限制，同时设置时两者都要匹配
+#[derive(Debug, Deserialize, Clone)]
+pub struct NotifierConfig {
+    /// 渠道名称，用于日志，digest 模式的邮件渠道据此区分历史文件。
+    /// 只能包含字母、数字、`_` 和 `-`，未设置时使用渠道类型，不能重复
+    pub name: Option<String>,
+    #[serde(default)]
+    pub notify_on: NotifyOn,
+    #[serde(default)]
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub databases: Vec<String>,
+    /// 该渠道的通知模板，覆盖全局 templates 中相同事件类型的模板
+    #[serde(default)]
+    pub templates: HashMap<String, MessageTemplate>,
+    #[serde(flatten)]
+    pub kind: NotifierKind,
+}
+
+impl NotifierConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(match self.kind {
+            NotifierKind::Webhook(_) => "webhook",
+            NotifierKind::DingTalk(_) => "dingtalk",
+            NotifierKind::WeCom(_) => "wecom",
+            NotifierKind::Feishu(_) => "feishu",
+            NotifierKind::Slack(_) => "slack",
+            NotifierKind::Telegram(_) => "telegram",
+            NotifierKind::Email(_) => "email",
+        })
+    }
+
+    fn channel(
+        &self,
+        app: &AppConfig,
+        templates: &HashMap<String, MessageTemplate>,
+    ) -> Result<Channel> {
+        let notifier: Box<dyn Notify> = match &self.kind {
+            NotifierKind::Webhook(config) => Box::new(WebHookNotify::new(config)),
+            NotifierKind::DingTalk(config) => Box::new(DingTalkNotify::new(config)),
+            NotifierKind::WeCom(config) => Box::new(WeComNotify::new(config)),
+            NotifierKind::Feishu(config) => Box::new(FeishuNotify::new(config)),
+            NotifierKind::Slack(config) => Box::new(SlackNotify::new(config)),
+            NotifierKind::Telegram(config) => Box::new(TelegramNotify::new(config)),
+            NotifierKind::Email(config) => Box::new(EmailNotify::new(
+                config,
+                app.notify_history_path(self.name()),
+            )?),
+        };
+        let mut channel = Channel::new(self.name(), notifier, self.notify_on);
+        channel.events = self.events.clone();
+        channel.databases = self.databases.clone();
+        channel.templates = templates.clone();
+        channel.templates.extend(self.templates.clone());
+        Ok(channel)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum NotifierKind {
+    /// 列表中的 webhook 使用外层的 notify_on
+    #[serde(rename = "webhook")]
+    Webhook(WebHookConfig),
+    #[serde(rename = "dingtalk")]
+    DingTalk(DingTalkConfig),
+    #[serde(rename = "wecom")]
+    WeCom(WeComConfig),
+    #[serde(rename = "feishu")]
+    Feishu(FeishuConfig),
+    #[serde(rename = "slack")]
+    Slack(SlackConfig),
+    #[serde(rename = "telegram")]
+    Telegram(TelegramConfig),
+    #[serde(rename = "email")]
+    Email(EmailConfig),
+}
+
+/// 钉钉机器人，url 为包含 access_token 的完整地址，secret 为加签密钥
+#[derive(Debug, Deserialize, Clone)]
+pub struct DingTalkConfig {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WeComConfig {
+    pub url: String,
+}
+
+/// 飞书/Lark 机器人，secret 为签名校验密钥
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeishuConfig {
+    pub url: String,
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SlackConfig {
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    pub chat_id: String,
+    #[serde(default = "default_telegram_api_url")]
+    pub api_url: String,
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+/// SMTP 邮件通知，port 未设置时按 security 使用 465/587/25
+#[derive(Debug, Deserialize, Clone)]
+pub struct EmailConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub mode: EmailMode,
+}
+
+/// SMTP 连接加密方式：tls 为隐式 TLS，starttls 为明文连接后升级，none 不加密（仅用于本地测试）
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SmtpSecurity {
+    #[serde(rename = "tls")]
+    Tls,
+    #[default]
+    #[serde(rename = "starttls")]
+    StartTls,
+    #[serde(rename = "none")]
+    None,
+}
+
+/// 邮件发送方式：event 每个事件一封，digest 由 digest 命令每天汇总发送
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum EmailMode {
+    #[default]
+    #[serde(rename = "event")]
+    Event,
+    #[serde(rename = "digest")]
+    Digest,
+}
+
+/// Prometheus 指标。textfile 为 node_exporter textfile collector 目录中的文件，
+/// 每次运行结束后更新；listen 为 metrics 命令提供 `/metrics` 的地址
+#[derive(Debug, Deserialize, Clone)]
+pub struct MetricsConfig {
+    pub textfile: Option<PathBuf>,
+    #[serde(default = "default_metrics_listen")]
+    pub listen: String,
+    /// 跨次运行累积指标的状态文件，默认 backup_dir/.metrics.json
+    pub state_file: Option<PathBuf>,
+}
+
+/// 默认只监听本机，需要远程抓取时显式配置监听地址
+pub(crate) fn default_metrics_listen() -> String {
+    "127.0.0.1:9185".into()
+}
+
+/// 日志输出。level 可被命令行 -v/-q 调整；设置 dir 后日志同时写入该目录下按 rotation
+/// 滚动的文件，只保留最新的 max_files 个
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub level: LogLevel,
+    #[serde(default)]
+    pub format: LogFormat,
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+    /// 失败通知附带的最近日志行数，0 表示不附带
+    #[serde(default = "default_notify_log_lines")]
+    pub notify_log_lines: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: LogLevel::default(),
+            format: LogFormat::default(),
+            dir: None,
+            rotation: LogRotation::default(),
+            max_files: default_log_max_files(),
+            notify_log_lines: default_notify_log_lines(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LogLevel {
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "warn")]
+    Warn,
+    #[default]
+    #[serde(rename = "info")]
+    Info,
+    #[serde(rename = "debug")]
+    Debug,
+    #[serde(rename = "trace")]
+    Trace,
+}
+
+/// human 为带颜色的单行文本，json 每行一个 JSON 对象，便于日志系统采集
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    #[default]
+    #[serde(rename = "human")]
+    Human,
+    #[serde(rename = "json")]
+    Json,
+}
+
+/// 日志文件的滚动周期，never 时始终写入同一个文件
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LogRotation {
+    #[serde(rename = "hourly")]
+    Hourly,
+    #[default]
+    #[serde(rename = "daily")]
+    Daily,
+    #[serde(rename = "never")]
+    Never,
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_notify_log_lines() -> usize {
+    20
+}
+
+/// OTLP/HTTP 导出各阶段的 span，需要以 `--features otel` 编译
+#[derive(Debug, Deserialize, Clone)]
+pub struct OtelConfig {
+    /// traces 接收地址，如 `http://localhost:4318/v1/traces`，
+    /// 未设置时使用 OTEL_EXPORTER_OTLP_TRACES_ENDPOINT 等环境变量
+    pub endpoint: Option<String>,
+    #[serde(default = "default_otel_service_name")]
+    pub service_name: String,
+    /// 附加的请求头，用于 Tempo 等需要认证的接收端
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+fn default_otel_service_name() -> String {
+    "backupdbtool".into()
+}
+
+/// 心跳监控。url 为 healthchecks.io 风格的基础地址，开始、成功、失败分别上报到
+/// `{url}/start`、`{url}`、`{url}/fail`，也可以用 start_url、success_url、fail_url 单独指定
+#[derive(Debug, Deserialize, Clone)]
+pub struct HealthcheckConfig {
+    /// 只对该任务生效，未设置时对 backup、upload、delete 都生效
+    pub job: Option<Job>,
+    pub url: Option<String>,
+    pub start_url: Option<String>,
+    pub success_url: Option<String>,
+    pub fail_url: Option<String>,
+    #[serde(default)]
+    pub method: PingMethod,
+    #[serde(default = "default_ping_timeout_secs")]
+    pub timeout_secs: u64,
+    /// 失败上报中附带的最近日志行数
+    #[serde(default = "default_ping_log_lines")]
+    pub log_lines: usize,
+}
+
+/// 心跳请求方式，get 用于只接受 GET 的监控（如旧版 Uptime Kuma），get 请求不带日志
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum PingMethod {
+    #[serde(rename = "get")]
+    Get,
+    #[default]
+    #[serde(rename = "post")]
+    Post,
+}
+
+fn default_ping_timeout_secs() -> u64 {
+    10
+}
+
+fn default_ping_log_lines() -> usize {
+    50
+}
+
+impl HealthcheckConfig {
+    fn base_url(&self, suffix: &str) -> Option<String> {
+        self.url
+            .as_ref()
+            .map(|url| format!("{}{}", url.trim_end_matches('/'), suffix))
+    }
+
+    pub fn start_url(&self) -> Option<String> {
+        self.start_url.clone().or_else(|| self.base_url("/start"))
+    }
+
+    pub fn success_url(&self) -> Option<String> {
+        self.success_url.clone().or_else(|| self.base_url(""))
+    }
+
+    pub fn fail_url(&self) -> Option<String> {
+        self.fail_url.clone().or_else(|| self.base_url("/fail"))
+    }
+}
+
+/// 通知模板，key 为事件类型（`backup.failed`、`backup`、`*.failed` 或 `*`），
+/// title 和 message 中可以使用 `{{database}}`、`{{size}}`、`{{duration}}`、`{{host}}`、`{{error}}` 等变量
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MessageTemplate {
+    pub title: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Webhook 请求体格式：legacy 为旧版 `{title, message}`，event 为带版本号的结构化事件
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum WebHookFormat {
+    #[default]
+    #[serde(rename = "legacy")]
+    Legacy,
+    #[serde(rename = "event")]
+    Event,
+}
+
+/// 通知发送时机
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum NotifyOn {
+    #[default]
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "failure")]
+    Failure,
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl NotifyOn {
+    pub fn on_success(&self) -> bool {
+        *self == NotifyOn::Always
+    }
+
+    pub fn on_failure(&self) -> bool {
+        *self != NotifyOn::Never
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq)]
@@ -99,6 +738,24 @@ pub enum CosProvider {
     LocalStorage,
     #[serde(rename = "s3")]
     S3,
+    #[serde(rename = "webdav")]
+    WebDav,
+    #[serde(rename = "gcs")]
+    Gcs,
+}
+
+impl CosProvider {
+    /// 与配置中的写法一致，用于日志和 span 属性
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CosProvider::TencentCos => "tencent_cos",
+            CosProvider::AliyunOss => "aliyun_oss",
+            CosProvider::LocalStorage => "local",
+            CosProvider::S3 => "s3",
+            CosProvider::WebDav => "webdav",
+            CosProvider::Gcs => "gcs",
+        }
+    }
 }
 
 impl Default for AppConfig {
@@ -113,6 +770,11 @@ impl Default for AppConfig {
             cos_provider: CosProvider::TencentCos,
             cos_path: "db/".into(),
             compress_password: "dbbackuppassword".into(),
+            upload_concurrency: default_upload_concurrency(),
+            skip_uploaded: default_skip_uploaded(),
+            after_upload: None,
+            min_keep_backups: default_min_keep_backups(),
+            lang: None,
         }
     }
 }
@@ -130,6 +792,16 @@ impl AppConfig {
         self.backup_dir.clone()
     }
 
+    pub fn metrics_state_path(&self) -> PathBuf {
+        self.backup_dir.join(".metrics.json")
+    }
+
+    /// 邮件日报使用的事件历史文件，按渠道名称每个渠道单独一个文件
+    pub fn notify_history_path(&self, name: &str) -> PathBuf {
+        self.backup_dir
+            .join(format!(".notify_history.{}.jsonl", name))
+    }
+
     pub fn database(&self, config: &AllConfig) -> Box<dyn Database> {
         match self.db_type {
             DbType::Postgresql => {
@@ -142,8 +814,12 @@ impl AppConfig {
             }
         }
     }
-    pub async fn storage(&self, config: &AllConfig) -> Arc<dyn Storage> {
-        match self.cos_provider {
+    pub async fn storage(&self, config: &AllConfig) -> Result<Arc<dyn Storage>> {
+        if let Some(bandwidth) = &config.bandwidth {
+            throttle::init(bandwidth)?;
+        }
+
+        let storage = match self.cos_provider {
             CosProvider::TencentCos => {
                 let config = &config.tencent_cos;
                 let storage = TencentCos::new(config);
@@ -155,8 +831,11 @@ impl AppConfig {
                 Arc::new(storage) as Arc<dyn Storage>
             }
             CosProvider::LocalStorage => {
-                let path = &config.app.get_backup_dir();
-                let storage = LocalStorage::new(path.to_str().unwrap()).await;
+                let config = config.local.as_ref().ok_or_else(|| {
+                    Error::InvalidConfig("local section is required for local provider".into())
+                })?;
+                let storage = LocalStorage::new(&config.path).await?;
+                storage.check_target(&self.backup_dir).await?;
                 Arc::new(storage) as Arc<dyn Storage>
             }
             CosProvider::S3 => {
@@ -164,7 +843,25 @@ impl AppConfig {
                 let storage = S3Oss::new(config);
                 Arc::new(storage) as Arc<dyn Storage>
             }
-        }
+            CosProvider::WebDav => {
+                let config = config.webdav.as_ref().ok_or_else(|| {
+                    Error::InvalidConfig("webdav section is required for webdav provider".into())
+                })?;
+                let storage = WebDav::new(config);
+                Arc::new(storage) as Arc<dyn Storage>
+            }
+            CosProvider::Gcs => {
+                let config = config.gcs.as_ref().ok_or_else(|| {
+                    Error::InvalidConfig("gcs section is required for gcs provider".into())
+                })?;
+                let storage = Gcs::new(config)?;
+                Arc::new(storage) as Arc<dyn Storage>
+            }
+        };
+
+        // 未配置 retry 时同样按默认策略重试
+        let retry = config.retry.clone().unwrap_or_default();
+        Ok(Arc::new(RetryStorage::new(storage, retry)))
     }
 }
 
@@ -188,10 +885,75 @@ pub fn get_all_config(config_path: &str, password: Option<String>) -> Result<All
     Ok(config)
 }
 
-pub fn get_webhook(config: &AllConfig) -> Option<WebHookNotify> {
-    config.webhook.as_ref().map(|webhook_config| {
-        WebHookNotify::new(webhook_config.url.clone(), webhook_config.token.clone())
-    })
+/// notifier 和 notifiers 中配置的渠道
+fn notifier_configs(config: &AllConfig) -> Result<Vec<&NotifierConfig>> {
+    let notifiers: Vec<&NotifierConfig> = config.notifier.iter().chain(&config.notifiers).collect();
+    check_notifier_names(&notifiers)?;
+    Ok(notifiers)
+}
+
+/// 渠道名称会用在历史文件名中，因此检查字符并拒绝重复的名称
+fn check_notifier_names(notifiers: &[&NotifierConfig]) -> Result<()> {
+    let mut names = HashSet::new();
+    for notifier in notifiers {
+        let name = notifier.name();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Error::InvalidConfig(format!(
+                "notifier name {:?} may only contain letters, digits, '_' and '-'",
+                name
+            )));
+        }
+        if !names.insert(name) {
+            return Err(Error::InvalidConfig(format!(
+                "duplicate notifier name {:?}, set a unique name for each notifier",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 构造通知分发器，notifier、notifiers 和 webhook 中配置的渠道都会生效
+pub fn get_notifier(config: &AllConfig) -> Result<Option<Box<dyn Notify>>> {
+    let mut channels = notifier_configs(config)?
+        .into_iter()
+        .map(|notifier| notifier.channel(&config.app, &config.templates))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(webhook_config) = &config.webhook {
+        let webhook = WebHookNotify::new(webhook_config);
+        let mut channel = Channel::new("webhook", Box::new(webhook), webhook_config.notify_on);
+        channel.templates = config.templates.clone();
+        channels.push(channel);
+    }
+
+    if channels.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(Box::new(Dispatcher::new(channels))))
+    }
+}
+
+/// 构造所有 digest 模式的邮件通知，用于 digest 命令
+pub fn get_email_digests(config: &AllConfig) -> Result<Vec<EmailNotify>> {
+    let digests = notifier_configs(config)?
+        .into_iter()
+        .filter_map(|notifier| match &notifier.kind {
+            NotifierKind::Email(email) if email.mode == EmailMode::Digest => Some(
+                EmailNotify::new(email, config.app.notify_history_path(notifier.name())),
+            ),
+            _ => None,
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if digests.is_empty() {
+        return Err(Error::InvalidConfig(
+            "digest requires an email notifier with mode: digest".into(),
+        ));
+    }
+    Ok(digests)
 }
 
 #[cfg(test)]
@@ -281,4 +1043,113 @@ mod tests {
         assert_eq!(config.s3.bucket, "bucket-1234567");
         assert_eq!(config.s3.region, Some("ap-shanghai".to_string()));
     }
+
+    #[test]
+    fn test_after_upload_keeps_newest_by_default() {
+        let after_upload: AfterUploadConfig = Config::builder()
+            .add_source(File::from_str("action: delete", config::FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(after_upload.action, AfterUploadAction::Delete);
+        assert_eq!(after_upload.keep_last, 1);
+    }
+
+    #[test]
+    fn test_notifier_config() {
+        #[derive(Deserialize)]
+        struct Notifiers {
+            notifiers: Vec<NotifierConfig>,
+        }
+
+        let content = r#"
+notifiers:
+  - type: "telegram"
+    name: "oncall"
+    notify_on: "failure"
+    databases: ["orders"]
+    bot_token: "123:abc"
+    chat_id: "-100200"
+  - type: "webhook"
+    events: ["*.succeeded"]
+    url: "https://example.com/hook"
+    templates:
+      "backup.succeeded":
+        message: "{{database}} {{size}}"
+      "*":
+        title: "Backup"
+"#;
+        let Notifiers { notifiers } = Config::builder()
+            .add_source(File::from_str(content, config::FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(notifiers[0].name(), "oncall");
+        assert_eq!(notifiers[0].notify_on, NotifyOn::Failure);
+        assert_eq!(notifiers[0].databases, vec!["orders"]);
+        let NotifierKind::Telegram(telegram) = &notifiers[0].kind else {
+            panic!("expected telegram notifier");
+        };
+        assert_eq!(telegram.chat_id, "-100200");
+        assert_eq!(telegram.api_url, "https://api.telegram.org");
+
+        assert_eq!(notifiers[1].name(), "webhook");
+        assert_eq!(notifiers[1].notify_on, NotifyOn::Always);
+        assert_eq!(notifiers[1].events, vec!["*.succeeded"]);
+        let template = &notifiers[1].templates["backup.succeeded"];
+        assert_eq!(template.message.as_deref(), Some("{{database}} {{size}}"));
+        assert_eq!(notifiers[1].templates["*"].title.as_deref(), Some("Backup"));
+    }
+
+    #[test]
+    fn test_check_notifier_names() {
+        #[derive(Deserialize)]
+        struct Notifiers {
+            notifiers: Vec<NotifierConfig>,
+        }
+        let parse = |content: &str| -> Vec<NotifierConfig> {
+            let Notifiers { notifiers } = Config::builder()
+                .add_source(File::from_str(content, config::FileFormat::Yaml))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap();
+            notifiers
+        };
+        let email = |name: &str| {
+            format!(
+                "  - type: email\n    {}host: smtp.example.com\n    from: a@example.com\n    to: [b@example.com]\n",
+                name
+            )
+        };
+
+        let named = parse(&format!(
+            "notifiers:\n{}{}",
+            email(""),
+            email("name: daily\n    ")
+        ));
+        check_notifier_names(&named.iter().collect::<Vec<_>>()).unwrap();
+
+        // 两个未命名的邮件渠道都使用默认名称 email，会共用历史文件
+        let unnamed = parse(&format!("notifiers:\n{}{}", email(""), email("")));
+        assert!(matches!(
+            check_notifier_names(&unnamed.iter().collect::<Vec<_>>()),
+            Err(Error::InvalidConfig(message)) if message.contains("duplicate")
+        ));
+
+        for name in ["../daily", "日报", "a b", "\"\""] {
+            let invalid = parse(&format!(
+                "notifiers:\n{}",
+                email(&format!("name: {}\n    ", name))
+            ));
+            assert!(
+                check_notifier_names(&invalid.iter().collect::<Vec<_>>()).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
 }
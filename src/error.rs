@@ -39,6 +39,12 @@ pub enum Error {
     #[error("Storage delete error for {key}: {message}")]
     StorageDelete { key: String, message: String },
 
+    #[error("Object {key} is locked: {message}")]
+    ObjectLocked { key: String, message: String },
+
+    #[error("Operation timed out: {0}")]
+    Timeout(String),
+
     #[error("Notification error: {0}")]
     Notification(String),
 
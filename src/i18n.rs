@@ -0,0 +1,319 @@
+use crate::error::Error;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::sync::RwLock;
+
+static LOCALE: RwLock<Locale> = RwLock::new(Locale::Zh);
+
+/// 命令行输出和通知使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+pub enum Locale {
+    #[serde(rename = "en")]
+    En,
+    #[serde(rename = "zh")]
+    Zh,
+}
+
+impl Locale {
+    /// 从 `LANG` 这类环境变量值中识别语言，例如 `zh_CN.UTF-8`、`en_US.UTF-8`
+    pub fn from_env_value(value: &str) -> Option<Locale> {
+        let value = value.to_ascii_lowercase();
+        if value.starts_with("zh") {
+            Some(Locale::Zh)
+        } else if value.starts_with("en") || value == "c" || value == "posix" {
+            Some(Locale::En)
+        } else {
+            None
+        }
+    }
+}
+
+/// 按优先级确定语言：`--lang` > 配置文件 `app.lang` > `LANG` 环境变量 > 中文。
+/// 配置加载后会再次调用以应用 `app.lang`
+pub fn init(cli: Option<Locale>, config: Option<Locale>) -> Locale {
+    let locale = cli
+        .or(config)
+        .or_else(|| {
+            std::env::var("LANG")
+                .ok()
+                .and_then(|value| Locale::from_env_value(&value))
+        })
+        .unwrap_or(Locale::Zh);
+    *LOCALE.write().unwrap_or_else(|e| e.into_inner()) = locale;
+    locale
+}
+
+pub fn locale() -> Locale {
+    *LOCALE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 消息目录中的条目，带 `{name}` 占位符的条目使用 `tf` 填充
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Msg {
+    HeaderKey,
+    HeaderModified,
+    HeaderSize,
+    HeaderDatabase,
+    HeaderBackupCount,
+    HeaderTotalSize,
+    HeaderNewest,
+    HeaderOldest,
+    HeaderFile,
+    HeaderStatus,
+    HeaderError,
+    HeaderBackupFile,
+    StatusUploaded,
+    StatusSkipped,
+    StatusDeleted,
+    StatusLocked,
+    StatusPending,
+    StatusFailed,
+    StatusSucceeded,
+    TitleFileList,
+    TitleSummary,
+    TitleBackupResult,
+    TitleUploadResult,
+    TitleDeleteResult,
+    TitleRemotePlan,
+    TitleLocalPlan,
+    NotifyProgress,
+    NotifyFailed,
+    FailureMessage,
+    StageDump,
+    StageCompress,
+    StageUpload,
+    StageDelete,
+    FieldHost,
+    FieldDuration,
+    DigestTitle,
+    DigestSummary,
+    BackupSucceeded,
+    FileUploaded,
+    UploadSummary,
+    ConfirmDeleteAll,
+    ErrIo,
+    ErrConfig,
+    ErrJson,
+    ErrYaml,
+    ErrEncryption,
+    ErrDecryption,
+    ErrDatabaseBackup,
+    ErrCompression,
+    ErrStorage,
+    ErrStorageUpload,
+    ErrStorageList,
+    ErrStorageDelete,
+    ErrObjectLocked,
+    ErrTimeout,
+    ErrNotification,
+    ErrPathResolution,
+    ErrInvalidConfig,
+    ErrCommandExecution,
+    ErrFileNotFound,
+    ErrCancelled,
+}
+
+/// (英文, 中文)
+fn catalog(msg: Msg) -> (&'static str, &'static str) {
+    match msg {
+        Msg::HeaderKey => ("Key", "文件路径"),
+        Msg::HeaderModified => ("Last modified", "修改时间"),
+        Msg::HeaderSize => ("Size", "大小"),
+        Msg::HeaderDatabase => ("Database", "数据库"),
+        Msg::HeaderBackupCount => ("Backups", "备份数量"),
+        Msg::HeaderTotalSize => ("Total size", "总大小"),
+        Msg::HeaderNewest => ("Newest", "最新备份"),
+        Msg::HeaderOldest => ("Oldest", "最早备份"),
+        Msg::HeaderFile => ("File", "文件"),
+        Msg::HeaderStatus => ("Status", "状态"),
+        Msg::HeaderError => ("Error", "错误"),
+        Msg::HeaderBackupFile => ("Backup file", "备份文件"),
+        Msg::StatusUploaded => ("uploaded", "已上传"),
+        Msg::StatusSkipped => ("skipped", "已跳过"),
+        Msg::StatusDeleted => ("deleted", "已删除"),
+        Msg::StatusLocked => ("locked", "已锁定"),
+        Msg::StatusPending => ("pending", "待删除"),
+        Msg::StatusFailed => ("failed", "失败"),
+        Msg::StatusSucceeded => ("succeeded", "成功"),
+        Msg::TitleFileList => ("Backup files", "COS 文件列表"),
+        Msg::TitleSummary => ("Backups per database", "数据库备份汇总"),
+        Msg::TitleBackupResult => ("Backup result", "备份结果"),
+        Msg::TitleUploadResult => ("Upload result", "上传结果"),
+        Msg::TitleDeleteResult => ("Delete result", "删除结果"),
+        Msg::TitleRemotePlan => ("Remote backups to delete", "待删除的远端备份"),
+        Msg::TitleLocalPlan => ("Local backups to delete", "待删除的本地备份"),
+        Msg::NotifyProgress => ("Backup progress", "备份进度"),
+        Msg::NotifyFailed => ("Backup job failed", "备份任务失败"),
+        Msg::FailureMessage => (
+            "Stage: {stage}\nDatabase: {database}\nHost: {host}\nError: {error}",
+            "阶段: {stage}\n数据库: {database}\n主机: {host}\n错误: {error}",
+        ),
+        Msg::StageDump => ("database dump", "数据库导出"),
+        Msg::StageCompress => ("compression", "压缩加密"),
+        Msg::StageUpload => ("upload", "上传"),
+        Msg::StageDelete => ("delete", "删除"),
+        Msg::FieldHost => ("Host", "主机"),
+        Msg::FieldDuration => ("Duration", "耗时"),
+        Msg::DigestTitle => ("Backup digest for {host}", "{host} 备份日报"),
+        Msg::DigestSummary => (
+            "{total} jobs in the last 24 hours: {succeeded} succeeded, {failed} failed",
+            "最近 24 小时共 {total} 个任务: 成功 {succeeded} 个, 失败 {failed} 个",
+        ),
+        Msg::BackupSucceeded => (
+            "Database {database} backed up successfully",
+            "数据库 {database} 备份成功",
+        ),
+        Msg::FileUploaded => ("{file} uploaded successfully", "{file} 上传成功"),
+        Msg::UploadSummary => (
+            "{total} files: {uploaded} uploaded, {skipped} skipped, {failed} failed",
+            "共 {total} 个文件: 上传成功 {uploaded} 个, 跳过 {skipped} 个, 失败 {failed} 个",
+        ),
+        Msg::ConfirmDeleteAll => (
+            "Delete {remote} remote and {local} local backups?",
+            "确认删除 {remote} 个远端备份和 {local} 个本地备份?",
+        ),
+        Msg::ErrIo => ("I/O error: {detail}", "I/O 错误: {detail}"),
+        Msg::ErrConfig => ("Configuration error: {detail}", "配置错误: {detail}"),
+        Msg::ErrJson => (
+            "JSON serialization error: {detail}",
+            "JSON 序列化错误: {detail}",
+        ),
+        Msg::ErrYaml => (
+            "YAML serialization error: {detail}",
+            "YAML 序列化错误: {detail}",
+        ),
+        Msg::ErrEncryption => ("Encryption error: {detail}", "加密错误: {detail}"),
+        Msg::ErrDecryption => ("Decryption error: {detail}", "解密错误: {detail}"),
+        Msg::ErrDatabaseBackup => (
+            "Database backup error: {detail}",
+            "数据库备份失败: {detail}",
+        ),
+        Msg::ErrCompression => ("Compression error: {detail}", "压缩失败: {detail}"),
+        Msg::ErrStorage => ("Storage error: {detail}", "存储错误: {detail}"),
+        Msg::ErrStorageUpload => (
+            "Storage upload error for {path}: {detail}",
+            "上传 {path} 失败: {detail}",
+        ),
+        Msg::ErrStorageList => ("Storage list error: {detail}", "列出文件失败: {detail}"),
+        Msg::ErrStorageDelete => (
+            "Storage delete error for {key}: {detail}",
+            "删除 {key} 失败: {detail}",
+        ),
+        Msg::ErrObjectLocked => (
+            "Object {key} is locked: {detail}",
+            "对象 {key} 已锁定: {detail}",
+        ),
+        Msg::ErrTimeout => ("Operation timed out: {detail}", "操作超时: {detail}"),
+        Msg::ErrNotification => ("Notification error: {detail}", "通知发送失败: {detail}"),
+        Msg::ErrPathResolution => ("Path resolution error: {detail}", "路径解析错误: {detail}"),
+        Msg::ErrInvalidConfig => ("Invalid configuration: {detail}", "配置无效: {detail}"),
+        Msg::ErrCommandExecution => (
+            "Command execution failed: {detail}",
+            "命令执行失败: {detail}",
+        ),
+        Msg::ErrFileNotFound => ("File not found: {path}", "文件不存在: {path}"),
+        Msg::ErrCancelled => (
+            "Operation cancelled or no action taken",
+            "操作已取消或未执行",
+        ),
+    }
+}
+
+/// 当前语言下的消息文本
+pub fn t(msg: Msg) -> &'static str {
+    let (en, zh) = catalog(msg);
+    match locale() {
+        Locale::En => en,
+        Locale::Zh => zh,
+    }
+}
+
+/// 当前语言下的消息文本，并用 args 替换 `{name}` 占位符
+pub fn tf(msg: Msg, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(t(msg).to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+/// 错误信息的本地化文本，细节部分保持原样
+pub fn error_message(error: &Error) -> String {
+    let detail = |msg: Msg, detail: &dyn Display| tf(msg, &[("detail", detail)]);
+    match error {
+        Error::Io(e) => detail(Msg::ErrIo, e),
+        Error::Config(e) => detail(Msg::ErrConfig, e),
+        Error::Json(e) => detail(Msg::ErrJson, e),
+        Error::Yaml(e) => detail(Msg::ErrYaml, e),
+        Error::Encryption(e) => detail(Msg::ErrEncryption, e),
+        Error::Decryption(e) => detail(Msg::ErrDecryption, e),
+        Error::DatabaseBackup(e) => detail(Msg::ErrDatabaseBackup, e),
+        Error::Compression(e) => detail(Msg::ErrCompression, e),
+        Error::Storage(e) => detail(Msg::ErrStorage, e),
+        Error::StorageUpload { path, message } => tf(
+            Msg::ErrStorageUpload,
+            &[("path", &path.display()), ("detail", message)],
+        ),
+        Error::StorageList(e) => detail(Msg::ErrStorageList, e),
+        Error::StorageDelete { key, message } => {
+            tf(Msg::ErrStorageDelete, &[("key", key), ("detail", message)])
+        }
+        Error::ObjectLocked { key, message } => {
+            tf(Msg::ErrObjectLocked, &[("key", key), ("detail", message)])
+        }
+        Error::Timeout(e) => detail(Msg::ErrTimeout, e),
+        Error::Notification(e) => detail(Msg::ErrNotification, e),
+        Error::PathResolution(e) => detail(Msg::ErrPathResolution, e),
+        Error::InvalidConfig(e) => detail(Msg::ErrInvalidConfig, e),
+        Error::CommandExecution(e) => detail(Msg::ErrCommandExecution, e),
+        Error::FileNotFound(path) => tf(Msg::ErrFileNotFound, &[("path", &path.display())]),
+        Error::Cancelled => t(Msg::ErrCancelled).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_env_value() {
+        assert_eq!(Locale::from_env_value("zh_CN.UTF-8"), Some(Locale::Zh));
+        assert_eq!(Locale::from_env_value("en_US.UTF-8"), Some(Locale::En));
+        assert_eq!(Locale::from_env_value("C"), Some(Locale::En));
+        assert_eq!(Locale::from_env_value("de_DE.UTF-8"), None);
+    }
+
+    #[test]
+    fn test_catalog_placeholders_match() {
+        // 中英文条目的占位符必须一致，否则某种语言下参数会丢失
+        let placeholders = |text: &str| {
+            let mut names: Vec<String> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+        for msg in [
+            Msg::BackupSucceeded,
+            Msg::FileUploaded,
+            Msg::FailureMessage,
+            Msg::UploadSummary,
+            Msg::ConfirmDeleteAll,
+            Msg::ErrStorageUpload,
+            Msg::ErrStorageDelete,
+            Msg::ErrObjectLocked,
+            Msg::ErrFileNotFound,
+        ] {
+            let (en, zh) = catalog(msg);
+            assert_eq!(placeholders(en), placeholders(zh), "{:?}", msg);
+        }
+    }
+
+    #[test]
+    fn test_tf_replaces_placeholders() {
+        let text = tf(Msg::UploadSummary, &[("total", &3), ("uploaded", &2)]);
+        assert!(text.contains('3') && text.contains('2'));
+        assert!(text.contains("{skipped}"));
+    }
+}
@@ -4,7 +4,11 @@ pub mod config;
 pub mod crypt;
 pub mod database;
 pub mod error;
+pub mod i18n;
+pub mod logging;
+pub mod metrics;
 pub mod notify;
+pub mod output;
 pub mod storage;
 pub mod utils;
 
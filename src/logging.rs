@@ -0,0 +1,491 @@
+use crate::config::{LogFormat, LogLevel, LogRotation, LoggingConfig, OtelConfig};
+use crate::error::Result;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use once_cell::sync::OnceCell;
+use serde_json::{Map, Value, json};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// 内存中保留的最近日志行数
+const TAIL_CAPACITY: usize = 200;
+
+/// 日志文件名前缀，滚动时文件名为 backupdbtool.{周期}.log
+const FILE_PREFIX: &str = "backupdbtool";
+
+static TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// 失败通知附带的日志行数，init 时按配置设置
+static NOTIFY_LOG_LINES: OnceCell<usize> = OnceCell::new();
+
+/// 初始化日志：stdout 留给命令结果，日志按配置的级别和格式写到 stderr 和滚动的日志文件，
+/// 同时在内存中保留本次运行最近的日志供失败上报使用。
+/// verbosity 为命令行 -v 次数减去 -q 次数；只有 OTLP 导出初始化失败时返回错误
+pub fn init(config: &LoggingConfig, verbosity: i8, otel: Option<&OtelConfig>) -> Result<()> {
+    let level = level_filter(config.level, verbosity);
+    let _ = NOTIFY_LOG_LINES.set(config.notify_log_lines);
+
+    let (file, file_error) = match &config.dir {
+        Some(dir) => match RollingFile::new(dir, config.rotation, config.max_files) {
+            Ok(file) => (Some(file), None),
+            Err(e) => (None, Some((dir, e))),
+        },
+        None => (None, None),
+    };
+
+    let registry = tracing_subscriber::registry();
+    #[cfg(feature = "otel")]
+    let (otel_layer, otel_result) = match otel.map(otel::layer).transpose() {
+        Ok(layer) => (layer, Ok(())),
+        Err(e) => (None, Err(e)),
+    };
+    #[cfg(feature = "otel")]
+    let registry = registry.with(otel_layer);
+    registry
+        .with(output(config.format, io::stderr, io::stderr().is_terminal()).with_filter(level))
+        .with(file.map(|file| output(config.format, Mutex::new(file), false).with_filter(level)))
+        // 安静模式下失败通知仍附带 info 日志
+        .with(LogTail.with_filter(level.max(LevelFilter::INFO)))
+        .init();
+
+    if let Some((dir, e)) = file_error {
+        warn!("Failed to open log file in {}: {}", dir.display(), e);
+    }
+
+    #[cfg(feature = "otel")]
+    return otel_result;
+
+    #[cfg(not(feature = "otel"))]
+    {
+        if otel.is_some() {
+            warn!("otel is configured but this build does not include the otel feature");
+        }
+        Ok(())
+    }
+}
+
+/// 退出前导出尚未发送的 span
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    otel::shutdown();
+}
+
+/// 最近 lines 行日志，按时间顺序以换行连接
+pub fn tail(lines: usize) -> String {
+    let tail = TAIL.lock().unwrap();
+    let skip = tail.len().saturating_sub(lines);
+    tail.iter()
+        .skip(skip)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 失败通知附带的本次运行日志，按 logging.notify_log_lines 截取，未开启或没有日志时为 None
+pub fn failure_log() -> Option<String> {
+    let lines = NOTIFY_LOG_LINES.get().copied().unwrap_or_default();
+    Some(tail(lines)).filter(|log| !log.is_empty())
+}
+
+/// 配置的级别按 -v 调高、按 -q 调低，最低为关闭
+fn level_filter(level: LogLevel, verbosity: i8) -> LevelFilter {
+    const LEVELS: [LevelFilter; 6] = [
+        LevelFilter::OFF,
+        LevelFilter::ERROR,
+        LevelFilter::WARN,
+        LevelFilter::INFO,
+        LevelFilter::DEBUG,
+        LevelFilter::TRACE,
+    ];
+    let base = match level {
+        LogLevel::Error => 1,
+        LogLevel::Warn => 2,
+        LogLevel::Info => 3,
+        LogLevel::Debug => 4,
+        LogLevel::Trace => 5,
+    };
+    let index = (base + verbosity as i32).clamp(0, LEVELS.len() as i32 - 1);
+    LEVELS[index as usize]
+}
+
+/// 按格式创建输出层，json 不带颜色
+fn output<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Human => layer.with_ansi(ansi).boxed(),
+        LogFormat::Json => layer.with_ansi(false).event_format(JsonFormat).boxed(),
+    }
+}
+
+/// 每条日志输出为一行 JSON，包含时间、级别、target、消息、字段和所在的 span
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+        let message = fields.remove("message").unwrap_or(Value::Null);
+        let spans: Vec<Value> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let extensions = span.extensions();
+                let fields = extensions
+                    .get::<FormattedFields<N>>()
+                    .map(|fields| fields.to_string())
+                    .unwrap_or_default();
+                json!({ "name": span.name(), "fields": fields })
+            })
+            .collect();
+        let line = json!({
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "message": message,
+            "fields": fields,
+            "spans": spans,
+        });
+        writeln!(writer, "{}", line)
+    }
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+}
+
+/// 按周期滚动的日志文件，写入时发现进入新周期就切换文件，只保留最新的 max_files 个。
+/// metrics 命令常驻运行时同样按周期切换
+struct RollingFile {
+    dir: PathBuf,
+    rotation: LogRotation,
+    max_files: usize,
+    period: String,
+    file: File,
+}
+
+impl RollingFile {
+    fn new(dir: &Path, rotation: LogRotation, max_files: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let period = period(rotation, Local::now());
+        let file = open_log(dir, &period)?;
+        let rolling = RollingFile {
+            dir: dir.to_path_buf(),
+            rotation,
+            max_files,
+            period,
+            file,
+        };
+        rolling.prune();
+        Ok(rolling)
+    }
+
+    /// 文件名中的周期按字典序即时间顺序，删除最旧的文件
+    fn prune(&self) {
+        if self.rotation == LogRotation::Never {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| {
+                name.starts_with(&format!("{}.", FILE_PREFIX))
+                    && name.ends_with(".log")
+                    && *name != log_name("")
+            })
+            .collect();
+        names.sort();
+        let excess = names.len().saturating_sub(self.max_files.max(1));
+        for name in &names[..excess] {
+            if let Err(e) = fs::remove_file(self.dir.join(name)) {
+                eprintln!("Failed to remove old log file {}: {}", name, e);
+            }
+        }
+    }
+}
+
+impl io::Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let period = period(self.rotation, Local::now());
+        if period != self.period {
+            self.file = open_log(&self.dir, &period)?;
+            self.period = period;
+            self.prune();
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn period(rotation: LogRotation, now: DateTime<Local>) -> String {
+    match rotation {
+        LogRotation::Hourly => now.format("%Y-%m-%d-%H").to_string(),
+        LogRotation::Daily => now.format("%Y-%m-%d").to_string(),
+        LogRotation::Never => String::new(),
+    }
+}
+
+fn log_name(period: &str) -> String {
+    if period.is_empty() {
+        format!("{}.log", FILE_PREFIX)
+    } else {
+        format!("{}.{}.log", FILE_PREFIX, period)
+    }
+}
+
+fn open_log(dir: &Path, period: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(log_name(period)))
+}
+
+/// 把每条日志格式化为一行写入环形缓冲区
+struct LogTail;
+
+impl<S: Subscriber> Layer<S> for LogTail {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = format!(
+            "{} {:>5} {}:",
+            Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+            metadata.level(),
+            metadata.target()
+        );
+        event.record(&mut LineVisitor(&mut line));
+
+        let mut tail = TAIL.lock().unwrap();
+        if tail.len() == TAIL_CAPACITY {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use crate::config::OtelConfig;
+    use crate::error::{Error, Result};
+    use once_cell::sync::OnceCell;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing::Subscriber;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::registry::LookupSpan;
+
+    static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+    /// 只导出 info 及以上的 span，不受 -q 影响，也不会混入依赖库的 debug span
+    pub(super) fn layer<S>(config: &OtelConfig) -> Result<impl Layer<S>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let mut builder = SpanExporter::builder()
+            .with_http()
+            .with_headers(config.headers.clone());
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        let exporter = builder
+            .build()
+            .map_err(|e| Error::InvalidConfig(format!("otel: {}", e)))?;
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        let _ = PROVIDER.set(provider);
+        Ok(tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(LevelFilter::INFO))
+    }
+
+    pub(super) fn shutdown() {
+        if let Some(provider) = PROVIDER.get()
+            && let Err(e) = provider.shutdown()
+        {
+            tracing::warn!("Failed to export spans: {}", e);
+        }
+    }
+}
+
+struct LineVisitor<'a>(&'a mut String);
+
+impl Visit for LineVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            let _ = write!(self.0, " {}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tail_keeps_latest_lines() {
+        let subscriber = tracing_subscriber::registry().with(LogTail);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..TAIL_CAPACITY + 5 {
+                tracing::info!(file = "a.7z", "line {}", i);
+            }
+        });
+
+        let tail = tail(2);
+        let lines: Vec<&str> = tail.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with(&format!("line {} file=\"a.7z\"", TAIL_CAPACITY + 4)));
+        assert!(lines[0].contains(" INFO "));
+    }
+
+    #[test]
+    fn test_level_filter() {
+        assert_eq!(level_filter(LogLevel::Info, 0), LevelFilter::INFO);
+        assert_eq!(level_filter(LogLevel::Info, 1), LevelFilter::DEBUG);
+        assert_eq!(level_filter(LogLevel::Info, 5), LevelFilter::TRACE);
+        assert_eq!(level_filter(LogLevel::Warn, -1), LevelFilter::ERROR);
+        assert_eq!(level_filter(LogLevel::Info, -3), LevelFilter::OFF);
+        assert_eq!(level_filter(LogLevel::Error, -5), LevelFilter::OFF);
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(output(
+            LogFormat::Json,
+            move || writer.clone(),
+            false,
+        ));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("upload", backend = "s3");
+            let _guard = span.enter();
+            tracing::warn!(bytes = 42u64, "Upload slow");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["message"], "Upload slow");
+        assert_eq!(line["fields"]["bytes"], 42);
+        assert_eq!(line["spans"][0]["name"], "upload");
+        assert_eq!(line["spans"][0]["fields"], "backend=\"s3\"");
+    }
+
+    #[test]
+    fn test_rolling_file_prunes_oldest() {
+        let dir = tempdir().unwrap();
+        for day in ["2026-01-01", "2026-01-02", "2026-01-03"] {
+            fs::write(dir.path().join(log_name(day)), "old").unwrap();
+        }
+        fs::write(dir.path().join("other.log"), "keep").unwrap();
+
+        let mut file = RollingFile::new(dir.path(), LogRotation::Daily, 2).unwrap();
+        io::Write::write_all(&mut file, b"line\n").unwrap();
+
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        let today = log_name(&period(LogRotation::Daily, Local::now()));
+        assert_eq!(
+            names,
+            vec![log_name("2026-01-03"), today.clone(), "other.log".into()]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join(today)).unwrap(),
+            "line\n"
+        );
+    }
+}
@@ -3,21 +3,26 @@
 use std::process;
 
 use backupdbtool::cli::args::{Cli, Commands};
-use backupdbtool::cli::command::{backup_database, delete_from_cos, upload_to_cos};
-use backupdbtool::config::{CosProvider, get_all_config, get_webhook};
+use backupdbtool::cli::command::{
+    backup_database, delete_from_cos, list_backups, send_digest, serve_metrics, upload_to_cos,
+};
+use backupdbtool::config::{LoggingConfig, get_all_config, get_notifier};
 use backupdbtool::error::Result;
-use backupdbtool::storage::CosItem;
-use backupdbtool::utils::{self, resolve_path};
+use backupdbtool::i18n;
+use backupdbtool::logging;
+use backupdbtool::metrics;
+use backupdbtool::notify::Job;
+use backupdbtool::notify::healthcheck::Healthcheck;
+use backupdbtool::utils::resolve_path;
 use clap::Parser;
 use std::path::PathBuf;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // 初始化日志
-    tracing_subscriber::fmt::init();
-
     let cli = Cli::parse();
+    // 配置加载前先按 --lang 和 LANG 确定语言，加载失败的错误信息也能本地化
+    i18n::init(cli.lang, None);
 
     let command = &cli.command;
 
@@ -26,20 +31,47 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
-    let config_path = cli.config.unwrap_or_else(|| {
-            error!("Configuration file path is required for this command. Please provide one using the --config option.");
-            process::exit(1);
-        });
+    // 日志配置在配置文件中，先加载配置再初始化日志，加载失败时按默认配置输出错误
+    let config = cli
+        .config
+        .as_deref()
+        .map(|path| get_all_config(path, cli.password.clone()));
+    let (logging_config, otel_config) = match &config {
+        Some(Ok(config)) => (config.logging.clone(), config.otel.clone()),
+        _ => (LoggingConfig::default(), None),
+    };
+    if let Err(e) = logging::init(&logging_config, cli.verbosity(), otel_config.as_ref()) {
+        error!("Failed to init otel: {}", i18n::error_message(&e));
+        process::exit(1);
+    }
+
+    let (Some(config_path), Some(config)) = (cli.config.clone(), config) else {
+        error!(
+            "Configuration file path is required for this command. Please provide one using the --config option."
+        );
+        process::exit(1);
+    };
     // 加载配置
-    let mut config = match get_all_config(&config_path, cli.password) {
+    let mut config = match config {
         Ok(config) => config,
         Err(e) => {
-            error!("Failed to load config: {}", e);
+            error!("Failed to load config: {}", i18n::error_message(&e));
             process::exit(1);
         }
     };
+    i18n::init(cli.lang, config.app.lang);
 
-    let notify = get_webhook(&config);
+    // 心跳在配置加载后立即开始，之后的初始化失败也按任务失败上报
+    let job = match cli.command {
+        Commands::Backup { .. } => Some(Job::Backup),
+        Commands::Upload { .. } => Some(Job::Upload),
+        Commands::Delete { .. } => Some(Job::Delete),
+        _ => None,
+    };
+    let healthcheck = job.map(|job| Healthcheck::new(&config.healthchecks, job));
+    if let Some(healthcheck) = &healthcheck {
+        healthcheck.start().await;
+    }
 
     let result = resolve_path(&config.app.backup_dir.to_string_lossy());
     match result {
@@ -47,20 +79,38 @@ async fn main() -> Result<()> {
             config.app.backup_dir = path;
         }
         Err(_) => {
-            error!(
+            let message = format!(
                 "please check the backup_dir path: {}",
                 &config.app.backup_dir.to_string_lossy()
             );
-            process::exit(1);
+            exit_with_failure(healthcheck.as_ref(), &message).await;
         }
     }
 
+    let notify = match get_notifier(&config) {
+        Ok(notify) => notify,
+        Err(e) => {
+            let message = format!("Failed to init notifier: {}", i18n::error_message(&e));
+            exit_with_failure(healthcheck.as_ref(), &message).await;
+        }
+    };
+
+    if let Some(metrics_config) = &config.metrics {
+        metrics::init(metrics_config, config.app.metrics_state_path());
+    }
+
     let app_config = &config.app;
     let _ = app_config.confirm_backup_dir().await;
     let db = app_config.database(&config);
-    let storage = app_config.storage(&config).await;
+    let storage = match app_config.storage(&config).await {
+        Ok(storage) => storage,
+        Err(e) => {
+            let message = format!("Failed to init storage: {}", i18n::error_message(&e));
+            exit_with_failure(healthcheck.as_ref(), &message).await;
+        }
+    };
 
-    match cli.command {
+    let result = match cli.command {
         Commands::Backup { database_name } => {
             info!("Starting backup for database: {}", database_name);
             backup_database(
@@ -69,17 +119,32 @@ async fn main() -> Result<()> {
                 &app_config.get_backup_dir(),
                 &app_config.compress_password,
                 notify,
+                cli.output,
             )
             .await
         }
         Commands::Upload { file, all } => {
             info!("Starting upload to COS");
-            upload_to_cos(file, all, app_config, storage, notify).await
+            upload_to_cos(file, all, app_config, storage, notify, cli.output).await
         }
-        Commands::Delete { key, all } => {
+        Commands::Delete {
+            key,
+            all,
+            dry_run,
+            yes,
+        } => {
             info!("Starting delete yesterday before file from  COS");
-            utils::cleanup_old_backups(&app_config.get_backup_dir()).await?;
-            delete_from_cos(key, all, storage.as_ref(),&app_config.cos_path).await
+            delete_from_cos(
+                key,
+                all,
+                dry_run,
+                yes,
+                storage.as_ref(),
+                app_config,
+                notify,
+                cli.output,
+            )
+            .await
         }
         Commands::Encrypt {
             destination,
@@ -92,15 +157,50 @@ async fn main() -> Result<()> {
                 &password,
             )
         }
-        Commands::List => {
-            let key_str = match &config.app.cos_provider {
-                CosProvider::LocalStorage => "*.7z",
-                _ => app_config.cos_path.as_str(),
-            };
-            let files: Vec<CosItem> = storage.list(key_str).await?;
-            let _ = utils::list_table(files);
-            Ok(())
+        Commands::List {
+            database,
+            since,
+            until,
+            limit,
+        } => {
+            list_backups(
+                database,
+                since,
+                until,
+                limit,
+                storage.as_ref(),
+                app_config,
+                cli.output,
+            )
+            .await
         }
+        Commands::Digest => send_digest(&config).await,
+        Commands::Metrics { listen } => serve_metrics(listen, &config).await,
         Commands::Version => Ok(()),
+    };
+
+    if let Err(e) = metrics::flush() {
+        warn!("Failed to write metrics: {}", i18n::error_message(&e));
+    }
+
+    match result {
+        Ok(()) => {
+            logging::shutdown();
+            if let Some(healthcheck) = &healthcheck {
+                healthcheck.success().await;
+            }
+        }
+        Err(e) => exit_with_failure(healthcheck.as_ref(), &i18n::error_message(&e)).await,
+    }
+    Ok(())
+}
+
+/// 记录错误并上报心跳失败后退出，请求体附带最近的日志
+async fn exit_with_failure(healthcheck: Option<&Healthcheck>, message: &str) -> ! {
+    error!("{}", message);
+    logging::shutdown();
+    if let Some(healthcheck) = healthcheck {
+        healthcheck.failure(1, message).await;
     }
+    process::exit(1);
 }
@@ -0,0 +1,464 @@
+use crate::config::MetricsConfig;
+use crate::error::{Error, Result};
+use crate::utils::BackupSummary;
+use chrono::Utc;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+/// 读取抓取请求的超时时间，避免不发完请求的连接一直占用
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Update = Box<dyn FnOnce(&mut MetricsState) + Send>;
+
+static RECORDER: OnceCell<Recorder> = OnceCell::new();
+
+/// 跨次运行累积的指标，保存在状态文件中，每次运行结束时更新
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MetricsState {
+    #[serde(default)]
+    pub databases: BTreeMap<String, DatabaseMetrics>,
+    #[serde(default)]
+    pub upload: UploadMetrics,
+    #[serde(default)]
+    pub remote: BTreeMap<String, RemoteMetrics>,
+    #[serde(default)]
+    pub deleted_total: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DatabaseMetrics {
+    pub last_success_timestamp: Option<i64>,
+    pub last_failure_timestamp: Option<i64>,
+    pub duration_seconds: f64,
+    pub size_bytes: u64,
+    pub successes_total: u64,
+    pub failures_total: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadMetrics {
+    pub last_success_timestamp: Option<i64>,
+    pub last_failure_timestamp: Option<i64>,
+    pub uploaded_total: u64,
+    pub skipped_total: u64,
+    pub failures_total: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RemoteMetrics {
+    pub count: usize,
+    pub size_bytes: u64,
+}
+
+impl MetricsState {
+    pub fn backup_succeeded(&mut self, database: &str, duration: Duration, size: u64) {
+        let metrics = self.databases.entry(database.to_string()).or_default();
+        metrics.last_success_timestamp = Some(Utc::now().timestamp());
+        metrics.duration_seconds = duration.as_secs_f64();
+        metrics.size_bytes = size;
+        metrics.successes_total += 1;
+    }
+
+    pub fn backup_failed(&mut self, database: &str) {
+        let metrics = self.databases.entry(database.to_string()).or_default();
+        metrics.last_failure_timestamp = Some(Utc::now().timestamp());
+        metrics.failures_total += 1;
+    }
+
+    pub fn upload_files(&mut self, uploaded: usize, skipped: usize, failed: usize) {
+        self.upload.uploaded_total += uploaded as u64;
+        self.upload.skipped_total += skipped as u64;
+        self.upload.failures_total += failed as u64;
+    }
+
+    pub fn upload_finished(&mut self, success: bool) {
+        let now = Some(Utc::now().timestamp());
+        if success {
+            self.upload.last_success_timestamp = now;
+        } else {
+            self.upload.last_failure_timestamp = now;
+        }
+    }
+
+    /// 用一次完整 list 的汇总替换远端备份统计
+    pub fn remote_backups(&mut self, summaries: &[BackupSummary]) {
+        self.remote = summaries
+            .iter()
+            .map(|summary| {
+                let metrics = RemoteMetrics {
+                    count: summary.count,
+                    size_bytes: summary.total_size,
+                };
+                (summary.database.clone(), metrics)
+            })
+            .collect();
+    }
+
+    pub fn deleted(&mut self, count: usize) {
+        self.deleted_total += count as u64;
+    }
+}
+
+/// 本次运行中记录的指标更新。结束时重新读取状态文件再应用更新，
+/// 缩小与并发运行的其他命令互相覆盖的窗口
+struct Recorder {
+    state_file: PathBuf,
+    textfile: Option<PathBuf>,
+    updates: Mutex<Vec<Update>>,
+}
+
+impl Recorder {
+    fn flush(&self) -> Result<()> {
+        let updates: Vec<Update> = std::mem::take(&mut *self.updates.lock().unwrap());
+        if updates.is_empty() {
+            return Ok(());
+        }
+        let mut state = load_state(&self.state_file)?;
+        for update in updates {
+            update(&mut state);
+        }
+        write_atomic(&self.state_file, &serde_json::to_string_pretty(&state)?)?;
+        if let Some(textfile) = &self.textfile {
+            write_atomic(textfile, &render(&state))?;
+        }
+        Ok(())
+    }
+}
+
+/// 配置了 metrics 时开始记录指标
+pub fn init(config: &MetricsConfig, default_state_file: PathBuf) {
+    let _ = RECORDER.set(Recorder {
+        state_file: config.state_file.clone().unwrap_or(default_state_file),
+        textfile: config.textfile.clone(),
+        updates: Mutex::new(Vec::new()),
+    });
+}
+
+/// 记录一次指标更新，未开启指标时忽略
+pub fn record(update: impl FnOnce(&mut MetricsState) + Send + 'static) {
+    if let Some(recorder) = RECORDER.get() {
+        recorder.updates.lock().unwrap().push(Box::new(update));
+    }
+}
+
+/// 写入状态文件和 node_exporter textfile
+pub fn flush() -> Result<()> {
+    match RECORDER.get() {
+        Some(recorder) => recorder.flush(),
+        None => Ok(()),
+    }
+}
+
+fn load_state(path: &Path) -> Result<MetricsState> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MetricsState::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 先写临时文件再重命名，避免 node_exporter 读到写了一半的文件
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// 一个指标族，labels 为空时输出不带标签的样本，没有样本时不输出
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+/// 按 Prometheus 文本格式输出
+pub fn render(state: &MetricsState) -> String {
+    let database = |name: &str| format!("{{database=\"{}\"}}", escape_label(name));
+    let per_database = |value: &dyn Fn(&DatabaseMetrics) -> Option<f64>| {
+        state
+            .databases
+            .iter()
+            .filter_map(|(name, metrics)| Some((database(name), value(metrics)?)))
+            .collect::<Vec<_>>()
+    };
+    let single = |value: Option<f64>| {
+        value
+            .map(|value| vec![(String::new(), value)])
+            .unwrap_or_default()
+    };
+
+    let mut out = String::new();
+    family(
+        &mut out,
+        "backup_last_success_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful backup.",
+        &per_database(&|m| m.last_success_timestamp.map(|t| t as f64)),
+    );
+    family(
+        &mut out,
+        "backup_last_failure_timestamp_seconds",
+        "gauge",
+        "Unix time of the last failed backup.",
+        &per_database(&|m| m.last_failure_timestamp.map(|t| t as f64)),
+    );
+    family(
+        &mut out,
+        "backup_duration_seconds",
+        "gauge",
+        "Duration of the last successful backup.",
+        &per_database(&|m| m.last_success_timestamp.map(|_| m.duration_seconds)),
+    );
+    family(
+        &mut out,
+        "backup_size_bytes",
+        "gauge",
+        "Size of the last successful backup archive.",
+        &per_database(&|m| m.last_success_timestamp.map(|_| m.size_bytes as f64)),
+    );
+    family(
+        &mut out,
+        "backup_success_total",
+        "counter",
+        "Number of successful backups.",
+        &per_database(&|m| Some(m.successes_total as f64)),
+    );
+    family(
+        &mut out,
+        "backup_failures_total",
+        "counter",
+        "Number of failed backups.",
+        &per_database(&|m| Some(m.failures_total as f64)),
+    );
+    family(
+        &mut out,
+        "upload_last_success_timestamp_seconds",
+        "gauge",
+        "Unix time of the last upload run without failures.",
+        &single(state.upload.last_success_timestamp.map(|t| t as f64)),
+    );
+    family(
+        &mut out,
+        "upload_last_failure_timestamp_seconds",
+        "gauge",
+        "Unix time of the last failed upload run.",
+        &single(state.upload.last_failure_timestamp.map(|t| t as f64)),
+    );
+    family(
+        &mut out,
+        "upload_files_total",
+        "counter",
+        "Number of uploaded backup files.",
+        &single(Some(state.upload.uploaded_total as f64)),
+    );
+    family(
+        &mut out,
+        "upload_skipped_total",
+        "counter",
+        "Number of backup files skipped because they were already uploaded.",
+        &single(Some(state.upload.skipped_total as f64)),
+    );
+    family(
+        &mut out,
+        "upload_failures_total",
+        "counter",
+        "Number of backup files that failed to upload.",
+        &single(Some(state.upload.failures_total as f64)),
+    );
+    let remote = |value: &dyn Fn(&RemoteMetrics) -> f64| {
+        state
+            .remote
+            .iter()
+            .map(|(name, metrics)| (database(name), value(metrics)))
+            .collect::<Vec<_>>()
+    };
+    family(
+        &mut out,
+        "remote_backup_count",
+        "gauge",
+        "Number of backups in remote storage as of the last list or delete.",
+        &remote(&|m| m.count as f64),
+    );
+    family(
+        &mut out,
+        "remote_backup_size_bytes",
+        "gauge",
+        "Total size of backups in remote storage as of the last list or delete.",
+        &remote(&|m| m.size_bytes as f64),
+    );
+    family(
+        &mut out,
+        "deleted_backups_total",
+        "counter",
+        "Number of expired remote backups deleted.",
+        &single(Some(state.deleted_total as f64)),
+    );
+    out
+}
+
+/// 在 listen 上提供 `/metrics`，每次抓取时读取最新的状态文件
+pub async fn serve(listen: &str, state_file: PathBuf) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .map_err(|e| Error::InvalidConfig(format!("cannot listen on {}: {}", listen, e)))?;
+    info!("Serving metrics on http://{}/metrics", listen);
+    serve_on(listener, state_file).await
+}
+
+async fn serve_on(listener: TcpListener, state_file: PathBuf) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state_file = state_file.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &state_file).await {
+                warn!("Failed to serve metrics request: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, state_file: &Path) -> Result<()> {
+    let mut buffer = vec![0u8; 8192];
+    let mut len = 0;
+    // 只需要请求行，读到请求头结束或缓冲区满为止，客户端迟迟不发完请求时断开连接
+    let read = async {
+        while len < buffer.len() {
+            let n = stream.read(&mut buffer[len..]).await?;
+            len += n;
+            if n == 0 || buffer[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read)
+        .await
+        .map_err(|_| Error::Timeout("reading metrics request".to_string()))??;
+    let request = String::from_utf8_lossy(&buffer[..len]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = if request.starts_with("GET ") && path == "/metrics" {
+        match load_state(state_file) {
+            Ok(state) => ("200 OK", render(&state)),
+            Err(e) => ("500 Internal Server Error", e.to_string()),
+        }
+    } else {
+        ("404 Not Found", "Not Found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_recorder_flush() {
+        let dir = tempdir().unwrap();
+        let recorder = Recorder {
+            state_file: dir.path().join("metrics.json"),
+            textfile: Some(dir.path().join("textfile/backupdbtool.prom")),
+            updates: Mutex::new(Vec::new()),
+        };
+
+        for _ in 0..2 {
+            recorder.updates.lock().unwrap().push(Box::new(|state| {
+                state.backup_succeeded("orders", Duration::from_millis(1500), 2048);
+                state.upload_files(1, 0, 1);
+            }));
+            recorder.flush().unwrap();
+        }
+
+        let textfile =
+            std::fs::read_to_string(dir.path().join("textfile/backupdbtool.prom")).unwrap();
+        assert!(textfile.contains("# TYPE backup_last_success_timestamp_seconds gauge\n"));
+        assert!(textfile.contains("backup_duration_seconds{database=\"orders\"} 1.5\n"));
+        assert!(textfile.contains("backup_size_bytes{database=\"orders\"} 2048\n"));
+        assert!(textfile.contains("backup_success_total{database=\"orders\"} 2\n"));
+        assert!(textfile.contains("upload_failures_total 2\n"));
+        assert!(!textfile.contains("backup_last_failure_timestamp_seconds"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        let dir = tempdir().unwrap();
+        let state_file = dir.path().join("metrics.json");
+        let mut state = MetricsState::default();
+        state.backup_succeeded("orders", Duration::from_millis(1500), 2048);
+        std::fs::write(&state_file, serde_json::to_string(&state).unwrap()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_on(listener, state_file));
+
+        let fetch = |request: &'static str| async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let response = fetch("GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("backup_size_bytes{database=\"orders\"} 2048\n"));
+        let response = fetch("GET / HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handle_closes_idle_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\n")
+            .await
+            .unwrap();
+
+        let result = handle(stream, Path::new("metrics.json")).await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.is_empty());
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,98 @@
+use super::{Event, Notify, check_response, hmac_sha256_base64};
+use crate::config::DingTalkConfig;
+use crate::error::{Error, Result};
+use chrono::Utc;
+use serde_json::json;
+use tracing::info;
+
+/// 钉钉自定义机器人，配置了 secret 时使用加签校验
+pub struct DingTalkNotify {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl DingTalkNotify {
+    pub fn new(config: &DingTalkConfig) -> Self {
+        DingTalkNotify {
+            client: reqwest::Client::new(),
+            url: config.url.clone(),
+            secret: config.secret.clone(),
+        }
+    }
+
+    /// 加签：以 secret 为密钥对 `timestamp\nsecret` 做 HmacSHA256，Base64 后 URL 编码
+    fn signed_url(&self, timestamp: i64) -> String {
+        let Some(secret) = &self.secret else {
+            return self.url.clone();
+        };
+        let string_to_sign = format!("{}\n{}", timestamp, secret);
+        let sign = hmac_sha256_base64(secret.as_bytes(), string_to_sign.as_bytes());
+        format!(
+            "{}&timestamp={}&sign={}",
+            self.url,
+            timestamp,
+            urlencoding::encode(&sign)
+        )
+    }
+}
+
+fn markdown(event: &Event) -> String {
+    let color = if event.is_success() {
+        "#52c41a"
+    } else {
+        "#f5222d"
+    };
+    let mut text = format!(
+        "### <font color=\"{}\">{}</font>\n\n{}\n",
+        color, event.title, event.message
+    );
+    for (label, value) in event.details() {
+        text.push_str(&format!("\n- **{}**: {}", label, value));
+    }
+    if let Some(logs) = event.attached_logs() {
+        text.push_str(&format!("\n\n```\n{}\n```", logs));
+    }
+    text
+}
+
+#[async_trait::async_trait]
+impl Notify for DingTalkNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        let body = json!({
+            "msgtype": "markdown",
+            "markdown": {
+                "title": event.title,
+                "text": markdown(event),
+            },
+        });
+        let res = self
+            .client
+            .post(self.signed_url(Utc::now().timestamp_millis()))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))?;
+        check_response("DingTalk", res).await?;
+        info!("DingTalk notification sent successfully");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_url() {
+        let notify = DingTalkNotify::new(&DingTalkConfig {
+            url: "https://oapi.dingtalk.com/robot/send?access_token=abc".to_string(),
+            secret: Some("SECtest".to_string()),
+        });
+        assert_eq!(
+            notify.signed_url(1700000000000),
+            "https://oapi.dingtalk.com/robot/send?access_token=abc\
+             &timestamp=1700000000000&sign=aZLLrriXgn05YbwaGR7knYsLeJADjr9NwLaNNKpxh4g%3D"
+        );
+    }
+}
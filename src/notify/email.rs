@@ -0,0 +1,197 @@
+use super::{Event, Notify, history};
+use crate::config::{EmailConfig, EmailMode, SmtpSecurity};
+use crate::error::{Error, Result};
+use crate::i18n::{Msg, t, tf};
+use crate::utils;
+use chrono::{Duration, Local, Utc};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use tracing::info;
+
+/// SMTP 邮件通知。event 模式每个事件发送一封邮件，
+/// digest 模式只把事件写入历史文件，由 `digest` 命令汇总最近 24 小时后发送
+pub struct EmailNotify {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    mode: EmailMode,
+    history: PathBuf,
+}
+
+fn mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| Error::InvalidConfig(format!("invalid email address {}: {}", address, e)))
+}
+
+impl EmailNotify {
+    pub fn new(config: &EmailConfig, history: PathBuf) -> Result<Self> {
+        let builder = match config.security {
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            }
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &config.host,
+            )),
+        }
+        .map_err(|e| Error::InvalidConfig(format!("invalid smtp host {}: {}", config.host, e)))?;
+
+        let port = config.port.unwrap_or(match config.security {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::None => 25,
+        });
+        let mut builder = builder.port(port);
+        if let Some(username) = &config.username {
+            let password = config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+
+        if config.to.is_empty() {
+            return Err(Error::InvalidConfig(
+                "email notifier requires at least one recipient".into(),
+            ));
+        }
+        Ok(EmailNotify {
+            transport: builder.build(),
+            from: mailbox(&config.from)?,
+            to: config
+                .to
+                .iter()
+                .map(|address| mailbox(address))
+                .collect::<Result<_>>()?,
+            mode: config.mode,
+            history,
+        })
+    }
+
+    async fn deliver(&self, subject: &str, body: String) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = builder
+            .body(body)
+            .map_err(|e| Error::Notification(e.to_string()))?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| Error::Notification(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 汇总最近 24 小时的事件发送一封邮件，没有事件时同样发送，便于确认任务仍在运行。
+    /// 返回汇总的事件数
+    pub async fn send_digest(&self) -> Result<usize> {
+        let since = Utc::now() - Duration::hours(24);
+        let events = history::since(&self.history, since).await?;
+        let (subject, body) = digest(&events);
+        self.deliver(&subject, body).await?;
+        history::prune(&self.history, since).await?;
+        info!("Email digest sent with {} events", events.len());
+        Ok(events.len())
+    }
+}
+
+fn event_body(event: &Event) -> String {
+    let mut body = format!("{}\n\n", event.message);
+    for (label, value) in event.details() {
+        body.push_str(&format!("{}: {}\n", label, value));
+    }
+    if let Some(logs) = event.attached_logs() {
+        body.push_str(&format!("\n{}\n", logs));
+    }
+    body.push_str(&format!(
+        "\n{} ({})\n",
+        event
+            .timestamp
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        event.event_type
+    ));
+    body
+}
+
+/// 生成日报的主题和正文：先是成功/失败统计，再按时间列出每个任务
+fn digest(events: &[Event]) -> (String, String) {
+    let succeeded = events.iter().filter(|event| event.is_success()).count();
+    let failed = events.len() - succeeded;
+    let subject = tf(Msg::DigestTitle, &[("host", &utils::hostname())]);
+    let mut body = tf(
+        Msg::DigestSummary,
+        &[
+            ("total", &events.len()),
+            ("succeeded", &succeeded),
+            ("failed", &failed),
+        ],
+    );
+    body.push_str("\n\n");
+
+    for event in events {
+        let time = event.timestamp.with_timezone(&Local).format("%m-%d %H:%M");
+        let status = if event.is_success() {
+            t(Msg::StatusSucceeded)
+        } else {
+            t(Msg::StatusFailed)
+        };
+        let database = event.database.as_deref().unwrap_or("-");
+        body.push_str(&format!(
+            "{}  {:<8} {:<7} {}",
+            time,
+            event.event_type.split('.').next().unwrap_or_default(),
+            status,
+            database
+        ));
+        match (&event.error, &event.archive_key) {
+            (Some(error), _) => body.push_str(&format!("  {}", error)),
+            (None, Some(key)) => body.push_str(&format!("  {}", key)),
+            (None, None) => {}
+        }
+        body.push('\n');
+    }
+    (subject, body)
+}
+
+#[async_trait::async_trait]
+impl Notify for EmailNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        match self.mode {
+            EmailMode::Event => {
+                let subject = format!("[{}] {}", event.title, event.host);
+                self.deliver(&subject, event_body(event)).await?;
+                info!("Email notification sent successfully");
+            }
+            EmailMode::Digest => history::append(&self.history, event).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Job, Stage};
+
+    #[test]
+    fn test_digest() {
+        let backup = Event::success(Job::Backup, "ok".to_string())
+            .with_database("orders")
+            .with_archive("db/orders_20241001_020000.7z".to_string(), 1024, None);
+        let error = Error::Storage("timeout".to_string());
+        let upload = Event::failure(Job::Upload, Stage::Upload, None, &error);
+
+        let (_, body) = digest(&[backup, upload]);
+        let lines: Vec<&str> = body.lines().collect();
+        assert!(lines[0].contains('2') && lines[0].contains('1'));
+        assert!(lines[2].contains("backup") && lines[2].contains("orders"));
+        assert!(lines[2].ends_with("db/orders_20241001_020000.7z"));
+        assert!(lines[3].contains("upload") && lines[3].contains("timeout"));
+    }
+}
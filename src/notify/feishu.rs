@@ -0,0 +1,87 @@
+use super::{Event, Notify, check_response, hmac_sha256_base64};
+use crate::config::FeishuConfig;
+use crate::error::{Error, Result};
+use chrono::Utc;
+use serde_json::{Value, json};
+use tracing::info;
+
+/// 飞书/Lark 自定义机器人，以消息卡片发送，配置了 secret 时启用签名校验
+pub struct FeishuNotify {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl FeishuNotify {
+    pub fn new(config: &FeishuConfig) -> Self {
+        FeishuNotify {
+            client: reqwest::Client::new(),
+            url: config.url.clone(),
+            secret: config.secret.clone(),
+        }
+    }
+
+    fn body(&self, event: &Event, timestamp: i64) -> Value {
+        let mut content = event.message.clone();
+        for (label, value) in event.details() {
+            content.push_str(&format!("\n**{}**: {}", label, value));
+        }
+        if let Some(logs) = event.attached_logs() {
+            content.push_str(&format!("\n```\n{}\n```", logs));
+        }
+        let template = if event.is_success() { "green" } else { "red" };
+        let mut body = json!({
+            "msg_type": "interactive",
+            "card": {
+                "header": {
+                    "title": { "tag": "plain_text", "content": event.title },
+                    "template": template,
+                },
+                "elements": [
+                    { "tag": "div", "text": { "tag": "lark_md", "content": content } },
+                ],
+            },
+        });
+        if let Some(secret) = &self.secret {
+            body["timestamp"] = json!(timestamp.to_string());
+            body["sign"] = json!(sign(secret, timestamp));
+        }
+        body
+    }
+}
+
+/// 飞书签名：以 `timestamp\nsecret` 为密钥对空字符串做 HmacSHA256，再 Base64，时间戳单位为秒
+fn sign(secret: &str, timestamp: i64) -> String {
+    let key = format!("{}\n{}", timestamp, secret);
+    hmac_sha256_base64(key.as_bytes(), b"")
+}
+
+#[async_trait::async_trait]
+impl Notify for FeishuNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        let body = self.body(event, Utc::now().timestamp());
+        let res = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))?;
+        check_response("Feishu", res).await?;
+        info!("Feishu notification sent successfully");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign("SECtest", 1700000000),
+            "G7XpBpG8NgG02fJOAhX6FRAObIljmFoxVReo8I62pEk="
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use super::Job;
+use crate::config::{HealthcheckConfig, PingMethod};
+use crate::{logging, utils};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 失败上报请求体的大小上限，healthchecks.io 最多保存 100KB
+const MAX_BODY_BYTES: usize = 10_000;
+
+/// 任务开始、成功、失败时调用的心跳地址（兼容 healthchecks.io、Uptime Kuma push 监控），
+/// 任务没有按时上报成功时由监控服务告警。上报失败只记录日志，不影响任务结果
+pub struct Healthcheck {
+    client: reqwest::Client,
+    targets: Vec<HealthcheckConfig>,
+}
+
+impl Healthcheck {
+    /// 选出与 job 对应的心跳配置，没有设置 job 的配置对所有任务生效
+    pub fn new(configs: &[HealthcheckConfig], job: Job) -> Self {
+        Healthcheck {
+            client: reqwest::Client::new(),
+            targets: configs
+                .iter()
+                .filter(|config| config.job.is_none_or(|target| target == job))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub async fn start(&self) {
+        for target in &self.targets {
+            if let Some(url) = target.start_url() {
+                self.ping(target, &url, String::new()).await;
+            }
+        }
+    }
+
+    pub async fn success(&self) {
+        for target in &self.targets {
+            if let Some(url) = target.success_url() {
+                self.ping(target, &url, String::new()).await;
+            }
+        }
+    }
+
+    /// 失败上报，请求体包含退出码、错误信息和最近的日志
+    pub async fn failure(&self, exit_status: i32, error: &str) {
+        for target in &self.targets {
+            if let Some(url) = target.fail_url() {
+                let body = failure_body(exit_status, error, &logging::tail(target.log_lines));
+                self.ping(target, &url, body).await;
+            }
+        }
+    }
+
+    async fn ping(&self, target: &HealthcheckConfig, url: &str, body: String) {
+        let request = match target.method {
+            PingMethod::Get => self.client.get(url),
+            PingMethod::Post => self.client.post(url).body(body),
+        };
+        let result = request
+            .timeout(Duration::from_secs(target.timeout_secs))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        match result {
+            Ok(_) => info!("Healthcheck ping sent"),
+            Err(e) => warn!("Failed to send healthcheck ping: {}", e.without_url()),
+        }
+    }
+}
+
+/// 退出码和错误信息始终保留，日志只用剩余的空间，过长时保留末尾部分
+fn failure_body(exit_status: i32, error: &str, log_tail: &str) -> String {
+    let header = format!("exit status: {}\nerror: {}\n\n", exit_status, error);
+    let budget = MAX_BODY_BYTES.saturating_sub(header.len());
+    header + &utils::truncate_head(log_tail, budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_body_keeps_header() {
+        let log_tail = format!("{}\n最后一行", "日志".repeat(MAX_BODY_BYTES));
+        let body = failure_body(1, "upload failed", &log_tail);
+        assert!(body.len() <= MAX_BODY_BYTES);
+        assert!(body.starts_with("exit status: 1\nerror: upload failed\n\n...\n"));
+        assert!(body.ends_with("最后一行"));
+        assert!(failure_body(2, "x", "log").starts_with("exit status: 2\nerror: x\n"));
+    }
+}
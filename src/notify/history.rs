@@ -0,0 +1,94 @@
+use super::Event;
+use crate::error::Result;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+/// 追加一条事件记录，每行一个 JSON
+pub async fn append(path: &Path, event: &Event) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+/// 读取 since 之后的事件，无法解析的行跳过
+pub async fn since(path: &Path, since: DateTime<Utc>) -> Result<Vec<Event>> {
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let events = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<Event>(line) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn!("Skipping invalid notification history entry: {}", e);
+                None
+            }
+        })
+        .filter(|event| event.timestamp >= since)
+        .collect();
+    Ok(events)
+}
+
+/// 只保留 since 之后的事件，避免历史文件无限增长
+pub async fn prune(path: &Path, since: DateTime<Utc>) -> Result<()> {
+    let events = self::since(path, since).await?;
+    let mut content = String::new();
+    for event in &events {
+        content.push_str(&serde_json::to_string(event)?);
+        content.push('\n');
+    }
+    if fs::try_exists(path).await? {
+        fs::write(path, content).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::{Event, Job};
+    use chrono::Duration;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_history_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let now = Utc::now();
+
+        let mut old = Event::success(Job::Backup, "old".to_string());
+        old.timestamp = now - Duration::hours(30);
+        let recent = Event::success(Job::Upload, "recent".to_string());
+        append(&path, &old).await.unwrap();
+        append(&path, &recent).await.unwrap();
+
+        let events = since(&path, now - Duration::hours(24)).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message, "recent");
+
+        prune(&path, now - Duration::hours(24)).await.unwrap();
+        let events = since(&path, now - Duration::days(7)).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(
+            since(&dir.path().join("missing.jsonl"), now)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -1,9 +1,463 @@
+pub mod dingtalk;
+pub mod email;
+pub mod feishu;
+pub mod healthcheck;
+pub mod history;
+pub mod slack;
+pub mod telegram;
+pub mod template;
 pub mod webhook;
-use serde::Serialize;
+pub mod wecom;
+use crate::config::{MessageTemplate, NotifyOn};
+use crate::error::{Error, Result};
+use crate::i18n::{self, Msg, t, tf};
+use crate::utils;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use humansize::{DECIMAL, format_size};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{Instrument, error, info_span};
+
+/// 事件结构的版本号，字段发生不兼容变化时递增
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// 失败通知附带日志的最大字节数，避免超过聊天机器人的消息长度限制
+const MAX_LOG_BYTES: usize = 3000;
 
 #[async_trait::async_trait]
 pub trait Notify: Send + Sync {
-    type SendData: Serialize;
-    type Error;
-    async fn send(&self, data: Self::SendData) -> Result<(), Self::Error>;
+    async fn send(&self, event: &Event) -> Result<()>;
+
+    /// 是否需要发送该状态的事件，调用方据此跳过构造事件的额外开销
+    fn accepts(&self, _status: EventStatus) -> bool {
+        true
+    }
+}
+
+/// 产生事件的任务
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Job {
+    Backup,
+    Upload,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    Success,
+    Failure,
+}
+
+/// 出错的环节
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Dump,
+    Compress,
+    Upload,
+    Delete,
+}
+
+impl Stage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stage::Dump => t(Msg::StageDump),
+            Stage::Compress => t(Msg::StageCompress),
+            Stage::Upload => t(Msg::StageUpload),
+            Stage::Delete => t(Msg::StageDelete),
+        }
+    }
+}
+
+/// 通知事件。title 和 message 是按当前语言生成的可读文本，
+/// 旧版 `{title, message}` 格式和聊天机器人直接使用它们
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub schema_version: u32,
+    pub event_type: String,
+    pub job: Job,
+    pub status: EventStatus,
+    pub title: String,
+    pub message: String,
+    pub host: String,
+    pub timestamp: DateTime<Utc>,
+    pub database: Option<String>,
+    pub duration_ms: Option<u64>,
+    /// backup 事件为本地备份文件名，upload 事件为上传后的对象 key
+    pub archive_key: Option<String>,
+    pub size: Option<u64>,
+    pub checksum: Option<String>,
+    pub stage: Option<Stage>,
+    pub error: Option<String>,
+    /// 失败时附带的本次运行最近日志
+    pub logs: Option<String>,
+    /// 正文来自用户模板，平台不再附加默认的详情行
+    #[serde(skip)]
+    pub templated: bool,
+}
+
+impl Event {
+    fn new(job: Job, status: EventStatus, title: &str, message: String) -> Self {
+        let job_name = match job {
+            Job::Backup => "backup",
+            Job::Upload => "upload",
+            Job::Delete => "delete",
+        };
+        let status_name = match status {
+            EventStatus::Success => "succeeded",
+            EventStatus::Failure => "failed",
+        };
+        Event {
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_type: format!("{}.{}", job_name, status_name),
+            job,
+            status,
+            title: title.to_string(),
+            message,
+            host: utils::hostname(),
+            timestamp: Utc::now(),
+            database: None,
+            duration_ms: None,
+            archive_key: None,
+            size: None,
+            checksum: None,
+            stage: None,
+            error: None,
+            logs: None,
+            templated: false,
+        }
+    }
+
+    pub fn success(job: Job, message: String) -> Self {
+        Event::new(job, EventStatus::Success, t(Msg::NotifyProgress), message)
+    }
+
+    pub fn failure(job: Job, stage: Stage, database: Option<&str>, error: &Error) -> Self {
+        let mut event = Event::new(
+            job,
+            EventStatus::Failure,
+            t(Msg::NotifyFailed),
+            String::new(),
+        );
+        let error = i18n::error_message(error);
+        event.message = tf(
+            Msg::FailureMessage,
+            &[
+                ("stage", &stage.label()),
+                ("database", &database.unwrap_or("-")),
+                ("host", &event.host),
+                ("error", &error),
+            ],
+        );
+        event.database = database.map(str::to_string);
+        event.stage = Some(stage);
+        event.error = Some(error);
+        event
+    }
+
+    pub fn with_database(mut self, database: &str) -> Self {
+        self.database = Some(database.to_string());
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration_ms = Some(duration.as_millis() as u64);
+        self
+    }
+
+    pub fn with_archive(mut self, key: String, size: u64, checksum: Option<String>) -> Self {
+        self.archive_key = Some(key);
+        self.size = Some(size);
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn with_logs(mut self, logs: Option<String>) -> Self {
+        self.logs = logs.map(|logs| utils::truncate_head(&logs, MAX_LOG_BYTES));
+        self
+    }
+
+    /// 平台在详情之后以代码块展示的日志，使用模板时由模板中的 `{{logs}}` 决定是否展示
+    pub fn attached_logs(&self) -> Option<&str> {
+        self.logs.as_deref().filter(|_| !self.templated)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == EventStatus::Success
+    }
+
+    /// 消息正文之外的附加信息，聊天机器人按各自格式逐行展示
+    pub fn details(&self) -> Vec<(&'static str, String)> {
+        let mut details = Vec::new();
+        if self.templated {
+            return details;
+        }
+        if self.is_success() {
+            if let Some(database) = &self.database {
+                details.push((t(Msg::HeaderDatabase), database.clone()));
+            }
+            details.push((t(Msg::FieldHost), self.host.clone()));
+        }
+        if let Some(key) = &self.archive_key {
+            details.push((t(Msg::HeaderBackupFile), key.clone()));
+        }
+        if let Some(size) = self.size {
+            details.push((t(Msg::HeaderSize), format_size(size, DECIMAL)));
+        }
+        if let Some(checksum) = &self.checksum {
+            details.push(("MD5", checksum.clone()));
+        }
+        if let Some(duration_ms) = self.duration_ms {
+            let duration = format!("{:.1}s", duration_ms as f64 / 1000.0);
+            details.push((t(Msg::FieldDuration), duration));
+        }
+        details
+    }
+}
+
+/// 通知渠道，按 notify_on、事件类型和数据库过滤后交给具体平台发送
+pub struct Channel {
+    pub name: String,
+    pub notifier: Box<dyn Notify>,
+    pub notify_on: NotifyOn,
+    /// 只发送这些类型的事件，支持 `backup.failed`、`backup`、`*.failed`，为空时不限制
+    pub events: Vec<String>,
+    /// 只发送这些数据库的事件，为空时不限制；不属于单个数据库的事件不会匹配
+    pub databases: Vec<String>,
+    /// 按事件类型配置的消息模板
+    pub templates: HashMap<String, MessageTemplate>,
+}
+
+impl Channel {
+    pub fn new(name: impl Into<String>, notifier: Box<dyn Notify>, notify_on: NotifyOn) -> Self {
+        Channel {
+            name: name.into(),
+            notifier,
+            notify_on,
+            events: Vec::new(),
+            databases: Vec::new(),
+            templates: HashMap::new(),
+        }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        let event_matched = self.events.is_empty()
+            || self
+                .events
+                .iter()
+                .any(|pattern| event_type_matches(pattern, &event.event_type));
+        let database_matched = self.databases.is_empty()
+            || event
+                .database
+                .as_ref()
+                .is_some_and(|database| self.databases.contains(database));
+        self.accepts(event.status) && event_matched && database_matched
+    }
+}
+
+/// 按 `.` 分段比较，`*` 匹配任意一段，只写任务名时匹配该任务的所有事件
+fn event_type_matches(pattern: &str, event_type: &str) -> bool {
+    let mut parts = event_type.split('.');
+    pattern.split('.').all(|expected| {
+        parts
+            .next()
+            .is_some_and(|part| expected == "*" || expected == part)
+    })
+}
+
+#[async_trait::async_trait]
+impl Notify for Channel {
+    async fn send(&self, event: &Event) -> Result<()> {
+        if !self.matches(event) {
+            return Ok(());
+        }
+        match template::find(&self.templates, event) {
+            Some(template) => self.notifier.send(&template::apply(template, event)).await,
+            None => self.notifier.send(event).await,
+        }
+    }
+
+    fn accepts(&self, status: EventStatus) -> bool {
+        match status {
+            EventStatus::Success => self.notify_on.on_success(),
+            EventStatus::Failure => self.notify_on.on_failure(),
+        }
+    }
+}
+
+/// 将事件并发发送到所有匹配的渠道，单个渠道失败不影响其他渠道，
+/// 全部发送结束后合并返回失败信息
+pub struct Dispatcher {
+    channels: Vec<Channel>,
+}
+
+impl Dispatcher {
+    pub fn new(channels: Vec<Channel>) -> Self {
+        Dispatcher { channels }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for Dispatcher {
+    #[tracing::instrument(name = "notify", skip_all, fields(event_type = %event.event_type))]
+    async fn send(&self, event: &Event) -> Result<()> {
+        let sends = self
+            .channels
+            .iter()
+            .filter(|channel| channel.matches(event))
+            .map(|channel| {
+                let span = info_span!("notify_channel", channel = %channel.name);
+                async move { (channel, channel.send(event).await) }.instrument(span)
+            });
+        let failures: Vec<String> = join_all(sends)
+            .await
+            .into_iter()
+            .filter_map(|(channel, result)| {
+                let e = result.err()?;
+                error!("Failed to send notification via {}: {}", channel.name, e);
+                Some(format!("{}: {}", channel.name, e))
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Notification(failures.join("; ")))
+        }
+    }
+
+    fn accepts(&self, status: EventStatus) -> bool {
+        self.channels.iter().any(|channel| channel.accepts(status))
+    }
+}
+
+/// 钉钉、飞书加签使用的 HmacSHA256 + Base64
+fn hmac_sha256_base64(key: &[u8], message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message);
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+/// 检查聊天机器人接口的返回，HTTP 200 时也可能在 JSON 中携带错误码
+async fn check_response(platform: &str, res: reqwest::Response) -> Result<()> {
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    let value: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+    let code = value
+        .get("errcode")
+        .or_else(|| value.get("code"))
+        .and_then(Value::as_i64)
+        .unwrap_or(0);
+    let ok = value.get("ok").and_then(Value::as_bool).unwrap_or(true);
+    if status.is_success() && code == 0 && ok {
+        Ok(())
+    } else {
+        Err(Error::Notification(format!(
+            "{} returned {}: {}",
+            platform, status, body
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_event_serialization() {
+        let error = Error::DatabaseBackup("pg_dump exited with 1".to_string());
+        let event = Event::failure(Job::Backup, Stage::Dump, Some("orders"), &error)
+            .with_duration(Duration::from_millis(1500));
+        let value = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(value["schema_version"], EVENT_SCHEMA_VERSION);
+        assert_eq!(value["event_type"], "backup.failed");
+        assert_eq!(value["job"], "backup");
+        assert_eq!(value["status"], "failure");
+        assert_eq!(value["stage"], "dump");
+        assert_eq!(value["database"], "orders");
+        assert_eq!(value["duration_ms"], 1500);
+        assert!(
+            value["error"]
+                .as_str()
+                .unwrap()
+                .contains("pg_dump exited with 1")
+        );
+        assert!(value["archive_key"].is_null());
+    }
+
+    /// 记录收到的事件类型，fail 为 true 时返回错误
+    pub(crate) struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Notify for Recorder {
+        async fn send(&self, event: &Event) -> Result<()> {
+            self.events.lock().unwrap().push(event.event_type.clone());
+            if self.fail {
+                Err(Error::Notification("unreachable".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn channel(
+        name: &str,
+        notify_on: NotifyOn,
+        fail: bool,
+    ) -> (Channel, Arc<Mutex<Vec<String>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            events: events.clone(),
+            fail,
+        };
+        (Channel::new(name, Box::new(recorder), notify_on), events)
+    }
+
+    #[test]
+    fn test_event_type_matches() {
+        assert!(event_type_matches("backup.failed", "backup.failed"));
+        assert!(event_type_matches("backup", "backup.succeeded"));
+        assert!(event_type_matches("*.failed", "upload.failed"));
+        assert!(!event_type_matches("*.failed", "upload.succeeded"));
+        assert!(!event_type_matches("backup.failed.extra", "backup.failed"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatcher_routing() {
+        let (mut oncall, oncall_events) = channel("oncall", NotifyOn::Failure, true);
+        oncall.databases = vec!["orders".to_string()];
+        let (mut quiet, quiet_events) = channel("quiet", NotifyOn::Always, false);
+        quiet.events = vec!["*.succeeded".to_string()];
+        let dispatcher = Dispatcher::new(vec![oncall, quiet]);
+
+        let error = Error::DatabaseBackup("exit 1".to_string());
+        let orders_failed = Event::failure(Job::Backup, Stage::Dump, Some("orders"), &error);
+        let users_failed = Event::failure(Job::Backup, Stage::Dump, Some("users"), &error);
+        let uploaded = Event::success(Job::Upload, "ok".to_string());
+
+        let result = dispatcher.send(&orders_failed).await;
+        assert!(
+            matches!(result, Err(Error::Notification(message)) if message.starts_with("oncall"))
+        );
+        dispatcher.send(&users_failed).await.unwrap();
+        dispatcher.send(&uploaded).await.unwrap();
+
+        assert_eq!(*oncall_events.lock().unwrap(), vec!["backup.failed"]);
+        assert_eq!(*quiet_events.lock().unwrap(), vec!["upload.succeeded"]);
+        assert!(dispatcher.accepts(EventStatus::Success));
+    }
 }
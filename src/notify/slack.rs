@@ -0,0 +1,56 @@
+use super::{Event, Notify, check_response};
+use crate::config::SlackConfig;
+use crate::error::{Error, Result};
+use serde_json::json;
+use tracing::info;
+
+/// Slack Incoming Webhook，使用 Block Kit 展示
+pub struct SlackNotify {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl SlackNotify {
+    pub fn new(config: &SlackConfig) -> Self {
+        SlackNotify {
+            client: reqwest::Client::new(),
+            url: config.url.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notify for SlackNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        let icon = if event.is_success() {
+            ":white_check_mark:"
+        } else {
+            ":x:"
+        };
+        let mut text = format!("{} {}", icon, event.message);
+        for (label, value) in event.details() {
+            text.push_str(&format!("\n*{}*: {}", label, value));
+        }
+        if let Some(logs) = event.attached_logs() {
+            text.push_str(&format!("\n```{}```", logs));
+        }
+        let body = json!({
+            // text 用于通知栏等不支持 blocks 的场景
+            "text": format!("{}: {}", event.title, event.message),
+            "blocks": [
+                { "type": "header", "text": { "type": "plain_text", "text": event.title } },
+                { "type": "section", "text": { "type": "mrkdwn", "text": text } },
+            ],
+        });
+        let res = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))?;
+        check_response("Slack", res).await?;
+        info!("Slack notification sent successfully");
+        Ok(())
+    }
+}
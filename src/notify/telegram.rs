@@ -0,0 +1,88 @@
+use super::{Event, Notify, check_response};
+use crate::config::TelegramConfig;
+use crate::error::{Error, Result};
+use serde_json::json;
+use tracing::info;
+
+/// Telegram Bot API，通过 sendMessage 发送 HTML 格式消息
+pub struct TelegramNotify {
+    client: reqwest::Client,
+    api_url: String,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl TelegramNotify {
+    pub fn new(config: &TelegramConfig) -> Self {
+        TelegramNotify {
+            client: reqwest::Client::new(),
+            api_url: config.api_url.trim_end_matches('/').to_string(),
+            bot_token: config.bot_token.clone(),
+            chat_id: config.chat_id.clone(),
+        }
+    }
+}
+
+/// HTML 模式下只需转义 <、>、&
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn html(event: &Event) -> String {
+    let icon = if event.is_success() { "✅" } else { "❌" };
+    let mut text = format!(
+        "{} <b>{}</b>\n{}\n",
+        icon,
+        escape_html(&event.title),
+        escape_html(&event.message)
+    );
+    for (label, value) in event.details() {
+        text.push_str(&format!(
+            "\n<b>{}</b>: <code>{}</code>",
+            escape_html(label),
+            escape_html(&value)
+        ));
+    }
+    if let Some(logs) = event.attached_logs() {
+        text.push_str(&format!("\n\n<pre>{}</pre>", escape_html(logs)));
+    }
+    text
+}
+
+#[async_trait::async_trait]
+impl Notify for TelegramNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        let url = format!("{}/bot{}/sendMessage", self.api_url, self.bot_token);
+        let body = json!({
+            "chat_id": self.chat_id,
+            "text": html(event),
+            "parse_mode": "HTML",
+        });
+        // 请求地址中包含 bot token，错误信息中去掉 URL 避免写入日志
+        let res = self
+            .client
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))?;
+        check_response("Telegram", res).await?;
+        info!("Telegram notification sent successfully");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("pg_dump <orders> & co"),
+            "pg_dump &lt;orders&gt; &amp; co"
+        );
+    }
+}
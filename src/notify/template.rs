@@ -0,0 +1,178 @@
+use super::Event;
+use crate::config::MessageTemplate;
+use chrono::Local;
+use humansize::{DECIMAL, format_size};
+use std::collections::HashMap;
+
+/// 按事件类型查找模板，优先级：完整类型 `backup.failed` > 任务 `backup` > `*.failed` > `*`
+pub fn find<'a>(
+    templates: &'a HashMap<String, MessageTemplate>,
+    event: &Event,
+) -> Option<&'a MessageTemplate> {
+    let (job, status) = event
+        .event_type
+        .split_once('.')
+        .unwrap_or((&event.event_type, ""));
+    [
+        event.event_type.clone(),
+        job.to_string(),
+        format!("*.{}", status),
+        "*".to_string(),
+    ]
+    .iter()
+    .find_map(|key| templates.get(key))
+}
+
+/// 用模板替换事件的标题和正文，模板中未设置的部分保留默认文本
+pub fn apply(template: &MessageTemplate, event: &Event) -> Event {
+    let variables = variables(event);
+    let mut rendered = event.clone();
+    if let Some(title) = &template.title {
+        rendered.title = render(title, &variables);
+    }
+    if let Some(message) = &template.message {
+        rendered.message = render(message, &variables);
+        rendered.templated = true;
+    }
+    rendered
+}
+
+/// 模板中可用的变量，事件中没有的字段为空字符串
+fn variables(event: &Event) -> HashMap<&'static str, String> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let job = event.event_type.split('.').next().unwrap_or_default();
+    HashMap::from([
+        ("event_type", event.event_type.clone()),
+        ("job", job.to_string()),
+        (
+            "status",
+            if event.is_success() {
+                "success"
+            } else {
+                "failure"
+            }
+            .to_string(),
+        ),
+        ("title", event.title.clone()),
+        ("message", event.message.clone()),
+        ("host", event.host.clone()),
+        (
+            "timestamp",
+            event
+                .timestamp
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string(),
+        ),
+        ("database", text(&event.database)),
+        (
+            "duration",
+            event
+                .duration_ms
+                .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                .unwrap_or_default(),
+        ),
+        (
+            "duration_ms",
+            event
+                .duration_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+        ),
+        ("archive_key", text(&event.archive_key)),
+        (
+            "size",
+            event
+                .size
+                .map(|size| format_size(size, DECIMAL))
+                .unwrap_or_default(),
+        ),
+        (
+            "size_bytes",
+            event.size.map(|size| size.to_string()).unwrap_or_default(),
+        ),
+        ("checksum", text(&event.checksum)),
+        (
+            "stage",
+            event
+                .stage
+                .map(|stage| stage.label().to_string())
+                .unwrap_or_default(),
+        ),
+        ("error", text(&event.error)),
+        ("logs", text(&event.logs)),
+    ])
+}
+
+/// 替换 `{{name}}` 占位符，名称两侧可以有空格，未知变量替换为空字符串
+fn render(template: &str, variables: &HashMap<&'static str, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + end].trim();
+        if let Some(value) = variables.get(name) {
+            output.push_str(value);
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::notify::{Job, Stage};
+
+    fn template(title: Option<&str>, message: &str) -> MessageTemplate {
+        MessageTemplate {
+            title: title.map(str::to_string),
+            message: Some(message.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let event = Event::success(Job::Backup, "ok".to_string())
+            .with_database("orders")
+            .with_archive("db/orders.7z".to_string(), 2_500_000, None);
+        let variables = variables(&event);
+        assert_eq!(
+            render(
+                "{{database}} {{ size }} {{unknown}}{{checksum}}|{{",
+                &variables
+            ),
+            "orders 2.50 MB |{{"
+        );
+    }
+
+    #[test]
+    fn test_find_and_apply() {
+        let templates = HashMap::from([
+            ("backup".to_string(), template(None, "backup {{database}}")),
+            (
+                "*.failed".to_string(),
+                template(Some("FAILED {{job}}"), "{{stage}}: {{error}}"),
+            ),
+        ]);
+        let error = Error::Storage("timeout".to_string());
+        let failed = Event::failure(Job::Upload, Stage::Upload, None, &error);
+        let succeeded = Event::success(Job::Backup, "ok".to_string()).with_database("orders");
+        let deleted = Event::success(Job::Delete, "ok".to_string());
+
+        let rendered = apply(find(&templates, &failed).unwrap(), &failed);
+        assert_eq!(rendered.title, "FAILED upload");
+        assert!(rendered.message.ends_with("timeout"));
+        assert!(rendered.details().is_empty());
+
+        let rendered = apply(find(&templates, &succeeded).unwrap(), &succeeded);
+        assert_eq!(rendered.title, succeeded.title);
+        assert_eq!(rendered.message, "backup orders");
+        assert!(find(&templates, &deleted).is_none());
+    }
+}
@@ -1,17 +1,27 @@
-use super::Notify;
+use super::{Event, Notify};
+use crate::config::{WebHookConfig, WebHookFormat, WebHookRetryConfig, WebHookSigning};
+use crate::error::{Error, Result};
+use crate::storage::retry::backoff;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
 use reqwest::{
-    Client,
-    header::{AUTHORIZATION, HeaderMap, HeaderValue},
+    Client, StatusCode,
+    header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue},
 };
 use serde::Serialize;
-use tracing::{error, info};
+use sha2::Sha256;
+use tracing::{info, warn};
 
 pub struct WebHookNotify {
     pub client: reqwest::Client,
     pub url: String,
     pub token: Option<String>,
+    pub format: WebHookFormat,
+    pub signing: Option<WebHookSigning>,
+    pub retry: WebHookRetryConfig,
 }
 
+/// 旧版请求体，只包含标题和消息文本
 #[derive(Serialize)]
 pub struct WebHookSendData {
     pub title: String,
@@ -27,9 +37,15 @@ impl WebHookSendData {
     }
 }
 
+impl From<&Event> for WebHookSendData {
+    fn from(event: &Event) -> Self {
+        WebHookSendData::new(event.title.clone(), event.message.clone())
+    }
+}
+
 impl WebHookNotify {
-    pub fn new(url: impl Into<String>, token: Option<String>) -> Self {
-        let client = if let Some(t) = token.clone() {
+    pub fn new(config: &WebHookConfig) -> Self {
+        let client = if let Some(t) = config.token.clone() {
             Client::builder()
                 .default_headers({
                     let mut headers = HeaderMap::new();
@@ -46,23 +62,182 @@ impl WebHookNotify {
         };
         WebHookNotify {
             client,
-            url: url.into(),
-            token,
+            url: config.url.clone(),
+            token: config.token.clone(),
+            format: config.format,
+            signing: config.signing.clone(),
+            retry: config.retry.clone(),
+        }
+    }
+
+    /// 发送一次请求，每次重试都重新生成时间戳和签名
+    async fn post(&self, body: &[u8]) -> Result<reqwest::Response> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(signing) = &self.signing {
+            let timestamp = Utc::now().timestamp();
+            request = request
+                .header(&signing.timestamp_header, timestamp.to_string())
+                .header(
+                    &signing.signature_header,
+                    signature(&signing.secret, timestamp, body),
+                );
         }
+        request
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))
     }
 }
 
+/// `sha256=` + hex(HmacSHA256(secret, "{timestamp}.{body}"))，
+/// 接收方按相同方式计算并比对，同时拒绝时间戳过旧的请求以防重放
+fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+/// 服务端错误、限流和请求超时可能在重试后成功，其他 4xx 重试也不会成功
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
 #[async_trait::async_trait]
 impl Notify for WebHookNotify {
-    type SendData = WebHookSendData;
-    type Error = reqwest::Error;
-    async fn send(&self, data: Self::SendData) -> Result<(), Self::Error> {
-        let res = self.client.post(&self.url).json(&data).send().await?;
-        if res.status().is_success() {
-            info!("Notification sent successfully");
-        } else {
-            error!("Failed to send notification {:?}", res.text().await);
+    async fn send(&self, event: &Event) -> Result<()> {
+        let body = match self.format {
+            WebHookFormat::Legacy => serde_json::to_vec(&WebHookSendData::from(event))?,
+            WebHookFormat::Event => serde_json::to_vec(event)?,
+        };
+
+        let attempts = self.retry.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let error = match self.post(&body).await {
+                Ok(res) if res.status().is_success() => {
+                    info!("Notification sent successfully");
+                    return Ok(());
+                }
+                Ok(res) => {
+                    let status = res.status();
+                    let error = Error::Notification(format!(
+                        "webhook returned {}: {}",
+                        status,
+                        res.text().await.unwrap_or_default()
+                    ));
+                    if !is_retryable(status) {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(e) => e,
+            };
+            if attempt >= attempts {
+                return Err(error);
+            }
+
+            let delay = backoff(self.retry.base_delay_ms, self.retry.max_delay_ms, attempt);
+            warn!(
+                "Webhook failed (attempt {}/{}), retry in {:?}: {}",
+                attempt, attempts, delay, error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
         }
-        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::Job;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 依次用 statuses 响应每个请求，返回收到的请求头
+    async fn serve(statuses: Vec<u16>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = vec![0u8; 8192];
+                let n = stream.read(&mut buffer).await.unwrap();
+                requests.push(String::from_utf8_lossy(&buffer[..n]).to_lowercase());
+                let response = format!(
+                    "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn config(url: String) -> WebHookConfig {
+        WebHookConfig {
+            url,
+            token: None,
+            notify_on: Default::default(),
+            format: WebHookFormat::Legacy,
+            signing: Some(WebHookSigning {
+                secret: "secret".to_string(),
+                signature_header: "X-Hub-Signature".to_string(),
+                timestamp_header: "X-Hub-Timestamp".to_string(),
+            }),
+            retry: WebHookRetryConfig {
+                attempts: 3,
+                base_delay_ms: 1,
+                max_delay_ms: 1,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_server_errors() {
+        let (url, server) = serve(vec![503, 200]).await;
+        let notify = WebHookNotify::new(&config(url));
+        let event = Event::success(Job::Backup, "ok".to_string());
+
+        notify.send(&event).await.unwrap();
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("x-hub-signature: sha256="));
+        assert!(requests[1].contains("x-hub-timestamp: "));
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_fast_on_client_errors() {
+        let (url, server) = serve(vec![401]).await;
+        let notify = WebHookNotify::new(&config(url));
+        let event = Event::success(Job::Backup, "ok".to_string());
+
+        let result = notify.send(&event).await;
+        assert!(matches!(result, Err(Error::Notification(message)) if message.contains("401")));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("secret", 1700000000, br#"{"title":"t"}"#),
+            "sha256=dc4ee97e24ed2230ad9c88329ae703170c16fda2707562be7d2d4e2b3b4f7946"
+        );
     }
 }
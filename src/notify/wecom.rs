@@ -0,0 +1,63 @@
+use super::{Event, Notify, check_response};
+use crate::config::WeComConfig;
+use crate::error::{Error, Result};
+use serde_json::json;
+use tracing::info;
+
+/// 企业微信群机器人
+pub struct WeComNotify {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WeComNotify {
+    pub fn new(config: &WeComConfig) -> Self {
+        WeComNotify {
+            client: reqwest::Client::new(),
+            url: config.url.clone(),
+        }
+    }
+}
+
+fn markdown(event: &Event) -> String {
+    // 企业微信 markdown 只支持 info(绿)、comment(灰)、warning(橙红) 三种颜色
+    let color = if event.is_success() {
+        "info"
+    } else {
+        "warning"
+    };
+    let mut content = format!(
+        "### <font color=\"{}\">{}</font>\n{}\n",
+        color, event.title, event.message
+    );
+    for (label, value) in event.details() {
+        content.push_str(&format!(
+            "\n> {}: <font color=\"comment\">{}</font>",
+            label, value
+        ));
+    }
+    if let Some(logs) = event.attached_logs() {
+        content.push_str(&format!("\n\n```\n{}\n```", logs));
+    }
+    content
+}
+
+#[async_trait::async_trait]
+impl Notify for WeComNotify {
+    async fn send(&self, event: &Event) -> Result<()> {
+        let body = json!({
+            "msgtype": "markdown",
+            "markdown": { "content": markdown(event) },
+        });
+        let res = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Notification(e.without_url().to_string()))?;
+        check_response("WeCom", res).await?;
+        info!("WeCom notification sent successfully");
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use crate::error::Result;
+use crate::i18n::{Msg, t};
+use crate::storage::CosItem;
+use crate::utils::{BackupSummary, UploadReport};
+use clap::ValueEnum;
+use humansize::{DECIMAL, format_size};
+use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow::{self, Borrowed};
+use std::path::PathBuf;
+use tabled::{Table, Tabled};
+
+/// 命令结果的输出格式，结果写到 stdout，日志统一写到 stderr
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+/// 单个文件在上传、删除中的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Uploaded,
+    Skipped,
+    Deleted,
+    Locked,
+    Pending,
+    Failed,
+}
+
+impl OutcomeStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            OutcomeStatus::Uploaded => t(Msg::StatusUploaded),
+            OutcomeStatus::Skipped => t(Msg::StatusSkipped),
+            OutcomeStatus::Deleted => t(Msg::StatusDeleted),
+            OutcomeStatus::Locked => t(Msg::StatusLocked),
+            OutcomeStatus::Pending => t(Msg::StatusPending),
+            OutcomeStatus::Failed => t(Msg::StatusFailed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileOutcome {
+    pub file: String,
+    pub status: OutcomeStatus,
+    pub error: Option<String>,
+}
+
+impl FileOutcome {
+    pub fn new(file: impl Into<String>, status: OutcomeStatus) -> Self {
+        FileOutcome {
+            file: file.into(),
+            status,
+            error: None,
+        }
+    }
+
+    pub fn failed(file: impl Into<String>, error: impl Into<String>) -> Self {
+        FileOutcome {
+            file: file.into(),
+            status: OutcomeStatus::Failed,
+            error: Some(error.into()),
+        }
+    }
+}
+
+impl Tabled for FileOutcome {
+    const LENGTH: usize = 3;
+    fn headers() -> Vec<Cow<'static, str>> {
+        vec![
+            Borrowed(t(Msg::HeaderFile)),
+            Borrowed(t(Msg::HeaderStatus)),
+            Borrowed(t(Msg::HeaderError)),
+        ]
+    }
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            self.file.as_str().into(),
+            self.status.label().into(),
+            self.error.as_deref().unwrap_or_default().into(),
+        ]
+    }
+}
+
+impl From<&UploadReport> for Vec<FileOutcome> {
+    fn from(report: &UploadReport) -> Self {
+        let display = |path: &PathBuf| path.display().to_string();
+        report
+            .uploaded
+            .iter()
+            .map(|path| FileOutcome::new(display(path), OutcomeStatus::Uploaded))
+            .chain(
+                report
+                    .skipped
+                    .iter()
+                    .map(|path| FileOutcome::new(display(path), OutcomeStatus::Skipped)),
+            )
+            .chain(
+                report
+                    .failed
+                    .iter()
+                    .map(|failure| FileOutcome::failed(display(&failure.path), &failure.error)),
+            )
+            .collect()
+    }
+}
+
+/// backup 命令生成的备份文件
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupResult {
+    pub database: String,
+    pub file: PathBuf,
+    pub size: u64,
+}
+
+impl Tabled for BackupResult {
+    const LENGTH: usize = 3;
+    fn headers() -> Vec<Cow<'static, str>> {
+        vec![
+            Borrowed(t(Msg::HeaderDatabase)),
+            Borrowed(t(Msg::HeaderBackupFile)),
+            Borrowed(t(Msg::HeaderSize)),
+        ]
+    }
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            self.database.as_str().into(),
+            self.file.display().to_string().into(),
+            format_size(self.size, DECIMAL).into(),
+        ]
+    }
+}
+
+/// 可以输出为 csv 的记录，COLUMNS 按顺序列出序列化后的字段名，
+/// 没有记录时也据此输出表头
+pub trait CsvRow: Serialize {
+    const COLUMNS: &'static [&'static str];
+}
+
+impl CsvRow for FileOutcome {
+    const COLUMNS: &'static [&'static str] = &["file", "status", "error"];
+}
+
+impl CsvRow for BackupResult {
+    const COLUMNS: &'static [&'static str] = &["database", "file", "size"];
+}
+
+impl CsvRow for CosItem {
+    const COLUMNS: &'static [&'static str] = &["key", "last_modified", "size"];
+}
+
+impl CsvRow for BackupSummary {
+    const COLUMNS: &'static [&'static str] =
+        &["database", "count", "total_size", "newest", "oldest"];
+}
+
+/// 按格式输出一组记录，table 格式带标题，csv 使用序列化后的原始字段
+pub fn print_rows<T: CsvRow + Tabled>(format: OutputFormat, title: &str, rows: &[T]) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            println!("=== {} ===", title);
+            println!("{}", Table::new(rows));
+        }
+        OutputFormat::Json => print_json(&rows)?,
+        OutputFormat::Csv => print!("{}", to_csv(rows)?),
+    }
+    Ok(())
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn to_csv<T: CsvRow>(rows: &[T]) -> Result<String> {
+    let mut csv = csv_line(T::COLUMNS.iter().copied());
+    for row in rows {
+        let row = serde_json::to_value(row)?;
+        let fields: Vec<String> = T::COLUMNS
+            .iter()
+            .map(|column| match &row[*column] {
+                Value::Null => String::new(),
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect();
+        csv.push_str(&csv_line(fields.iter().map(String::as_str)));
+    }
+    Ok(csv)
+}
+
+/// 按 RFC 4180 转义包含逗号、引号或换行的字段
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv() {
+        let rows = vec![
+            FileOutcome::new("db/a.7z", OutcomeStatus::Uploaded),
+            FileOutcome::failed("db/b,c.7z", "HTTP \"500\""),
+        ];
+        let csv = to_csv(&rows).unwrap();
+        assert_eq!(
+            csv,
+            "file,status,error\n\
+             db/a.7z,uploaded,\n\
+             \"db/b,c.7z\",failed,\"HTTP \"\"500\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_to_csv_empty_has_headers() {
+        assert_eq!(to_csv::<FileOutcome>(&[]).unwrap(), "file,status,error\n");
+        assert_eq!(
+            to_csv::<BackupSummary>(&[]).unwrap(),
+            "database,count,total_size,newest,oldest\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_columns_match_fields() {
+        let item = CosItem {
+            key: "db/a.7z".to_string(),
+            last_modified: chrono::Utc::now(),
+            size: 1,
+        };
+        let value = serde_json::to_value(&item).unwrap();
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let mut columns = CosItem::COLUMNS.to_vec();
+        keys.sort();
+        columns.sort();
+        assert_eq!(keys, columns);
+    }
+}
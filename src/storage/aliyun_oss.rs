@@ -1,7 +1,9 @@
 use super::CosItem;
-use crate::config::AliyunOssConfig;
+use crate::config::{AliyunOssConfig, ObjectOptions};
 use crate::error::{Error, Result};
-use crate::storage::Storage;
+use crate::storage::s3_compatible::{list_objects, put_object};
+use crate::storage::{Storage, object_headers};
+use crate::utils;
 use chrono::{DateTime, Utc};
 use s3::{Bucket, Region, creds::Credentials};
 use serde::{Deserialize, Serialize};
@@ -31,6 +33,8 @@ impl From<AliyunOssItem> for CosItem {
 #[derive(Debug, Clone)]
 pub struct AliyunOss {
     pub client: Box<Bucket>,
+    pub object_options: ObjectOptions,
+    pub max_keys: Option<u32>,
 }
 
 #[async_trait::async_trait]
@@ -43,11 +47,7 @@ impl Storage for AliyunOss {
             })?
             .to_string_lossy();
 
-        let s3_key = if cos_path.ends_with('/') {
-            format!("{}{}", cos_path, file_name)
-        } else {
-            format!("{}/{}", cos_path, file_name)
-        };
+        let s3_key = utils::object_key(cos_path, &file_name);
 
         // 读取文件内容
         let content = std::fs::read(file_path).map_err(|e| Error::StorageUpload {
@@ -55,64 +55,28 @@ impl Storage for AliyunOss {
             message: format!("Failed to read file: {}", e),
         })?;
 
+        let headers = object_headers(
+            &self.object_options,
+            "x-oss",
+            "x-oss-server-side-encryption-key-id",
+        )?;
+
         // 上传到 Aliyun Oss
-        let res = self
-            .client
-            .put_object(&s3_key, &content)
-            .await
-            .map_err(|e| Error::StorageUpload {
-                path: file_path.to_path_buf(),
-                message: e.to_string(),
-            })?;
+        let status_code = put_object(&self.client, &s3_key, content, headers, file_path).await?;
 
-        if res.status_code() == 200 {
+        if status_code == 200 {
             info!("Successfully uploaded: {}", file_name,);
             Ok(())
         } else {
             Err(Error::StorageUpload {
                 path: file_path.to_path_buf(),
-                message: format!("HTTP code: {}", res.status_code()),
+                message: format!("HTTP code: {}", status_code),
             })
         }
     }
 
     async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
-        let result = self
-            .client
-            .list(key.to_string(), None)
-            .await
-            .map_err(|e| Error::StorageList(e.to_string()))?;
-
-        let mut all_items = Vec::new();
-        for item in result {
-            let contents = item.contents;
-            let items: Vec<CosItem> = contents
-                .into_iter()
-                .filter_map(|object| {
-                    // Try to parse the last_modified string into DateTime<Utc>
-                    match DateTime::parse_from_rfc3339(&object.last_modified)
-                        .map(|dt| dt.with_timezone(&Utc))
-                    {
-                        Ok(last_modified) => Some(CosItem {
-                            key: object.key.clone(),
-                            last_modified,
-                            size: object.size,
-                        }),
-                        Err(e) => {
-                            info!(
-                                "Failed to parse last_modified: {} ({})",
-                                object.last_modified, e
-                            );
-                            None
-                        }
-                    }
-                })
-                .collect();
-
-            all_items.extend(items);
-        }
-
-        Ok(all_items)
+        list_objects(&self.client, key, self.max_keys).await
     }
 
     async fn delete(&self, key: &str) -> Result<()> {
@@ -158,6 +122,10 @@ impl AliyunOss {
         let bucket = Bucket::new(&config.bucket, region, credentials)
             .expect("create aliyun oss bucket failed");
 
-        AliyunOss { client: bucket }
+        AliyunOss {
+            client: bucket,
+            object_options: config.object_options.clone(),
+            max_keys: config.max_keys,
+        }
     }
 }
@@ -0,0 +1,490 @@
+use super::CosItem;
+use crate::config::GcsConfig;
+use crate::error::{Error, Result};
+use crate::storage::{Storage, reached_max_keys, sort_by_last_modified, throttle};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, HeaderMap, LOCATION, RANGE};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_with::{DisplayFromStr, serde_as};
+use std::io::SeekFrom;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Mutex;
+use tracing::info;
+
+const SCOPE: &str = "https://www.googleapis.com/auth/devstorage.read_write";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+// 可恢复上传的分块大小必须是 256 KiB 的整数倍
+const CHUNK_ALIGN: usize = 256 * 1024;
+const DEFAULT_CHUNK_SIZE: usize = 32 * CHUNK_ALIGN;
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GcsItem {
+    pub name: String,
+    pub updated: DateTime<Utc>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub size: u64,
+}
+
+impl From<GcsItem> for CosItem {
+    fn from(item: GcsItem) -> Self {
+        CosItem {
+            key: item.name,
+            last_modified: item.updated,
+            size: item.size,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcsListResponse {
+    #[serde(default)]
+    items: Vec<GcsItem>,
+    next_page_token: Option<String>,
+}
+
+/// 服务账号 JSON 密钥文件中用到的字段
+#[derive(Debug, Deserialize, Clone)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    pub token_uri: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+#[derive(Debug, Clone)]
+struct AccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub struct Gcs {
+    pub client: Client,
+    pub bucket: String,
+    pub endpoint: String,
+    pub chunk_size: usize,
+    pub max_keys: Option<u32>,
+    key: Option<ServiceAccountKey>,
+    token: Mutex<Option<AccessToken>>,
+}
+
+#[async_trait::async_trait]
+impl Storage for Gcs {
+    async fn upload(&self, file_path: &Path, cos_path: &str) -> Result<()> {
+        let upload_err = |message: String| Error::StorageUpload {
+            path: file_path.to_path_buf(),
+            message,
+        };
+
+        let file_name = file_path
+            .file_name()
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!("Invalid file path: {}", file_path.display()))
+            })?
+            .to_string_lossy();
+
+        let object_name = utils::object_key(cos_path, &file_name);
+
+        let mut file = tokio::fs::File::open(file_path)
+            .await
+            .map_err(|e| upload_err(format!("Failed to open file: {}", e)))?;
+        let total = file
+            .metadata()
+            .await
+            .map_err(|e| upload_err(format!("Failed to read file metadata: {}", e)))?
+            .len();
+
+        // 1. 创建可恢复上传会话
+        let url = format!(
+            "{}/upload/storage/v1/b/{}/o?uploadType=resumable&name={}",
+            self.endpoint,
+            urlencoding::encode(&self.bucket),
+            urlencoding::encode(&object_name)
+        );
+        let res = self
+            .authorize(self.client.post(&url))
+            .await?
+            .header("X-Upload-Content-Type", "application/octet-stream")
+            .header("X-Upload-Content-Length", total)
+            .header(CONTENT_LENGTH, 0)
+            .send()
+            .await
+            .map_err(|e| upload_err(e.to_string()))?;
+        if !res.status().is_success() {
+            return Err(upload_err(format!(
+                "Failed to start resumable upload, HTTP code: {}",
+                res.status()
+            )));
+        }
+        let session_uri = res
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| upload_err("Missing resumable session uri".to_string()))?
+            .to_string();
+
+        // 2. 分块上传，每次从服务端确认已持久化的位置继续，未确认的部分会被重新发送
+        let mut offset: u64 = 0;
+        let mut buffer = vec![0u8; self.chunk_size];
+        loop {
+            let want = (total - offset).min(buffer.len() as u64) as usize;
+            file.seek(SeekFrom::Start(offset))
+                .await
+                .map_err(|e| upload_err(format!("Failed to seek file: {}", e)))?;
+            let mut filled = 0;
+            while filled < want {
+                let n = file
+                    .read(&mut buffer[filled..want])
+                    .await
+                    .map_err(|e| upload_err(format!("Failed to read file: {}", e)))?;
+                if n == 0 {
+                    return Err(upload_err(format!(
+                        "File shrank during upload: expected {} bytes, got {}",
+                        total,
+                        offset + filled as u64
+                    )));
+                }
+                filled += n;
+            }
+
+            // 没有剩余数据时发送空请求，让服务端完成上传
+            let content_range = if filled == 0 {
+                format!("bytes */{}", total)
+            } else {
+                format!("bytes {}-{}/{}", offset, offset + filled as u64 - 1, total)
+            };
+
+            let res = self
+                .client
+                .put(&session_uri)
+                .header(CONTENT_RANGE, content_range)
+                .header(CONTENT_LENGTH, filled)
+                .body(throttle::body(buffer[..filled].to_vec()))
+                .send()
+                .await
+                .map_err(|e| upload_err(e.to_string()))?;
+
+            match res.status() {
+                StatusCode::OK | StatusCode::CREATED => break,
+                // 308 表示服务端等待后续数据，Range 为已持久化的范围，可能少于已发送的数据
+                StatusCode::PERMANENT_REDIRECT => {
+                    let persisted = persisted_offset(res.headers()).unwrap_or(0);
+                    if filled == 0 || persisted <= offset || persisted > total {
+                        return Err(upload_err(format!(
+                            "Resumable upload made no progress at byte {}",
+                            offset
+                        )));
+                    }
+                    offset = persisted;
+                }
+                status => {
+                    return Err(upload_err(format!("HTTP code: {}", status)));
+                }
+            }
+        }
+
+        info!(
+            "Successfully uploaded: {} to gs://{}/{}",
+            file_name, self.bucket, object_name
+        );
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<CosItem>> {
+        let url = format!(
+            "{}/storage/v1/b/{}/o",
+            self.endpoint,
+            urlencoding::encode(&self.bucket)
+        );
+
+        let mut all_items = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut query = vec![("prefix", prefix.to_string())];
+            if let Some(max_keys) = self.max_keys {
+                query.push(("maxResults", max_keys.to_string()));
+            }
+            if let Some(token) = &page_token {
+                query.push(("pageToken", token.clone()));
+            }
+
+            let res = self
+                .authorize(self.client.get(&url).query(&query))
+                .await?
+                .send()
+                .await
+                .map_err(|e| Error::StorageList(e.to_string()))?;
+            if !res.status().is_success() {
+                return Err(Error::StorageList(format!("HTTP code: {}", res.status())));
+            }
+
+            let page: GcsListResponse = res
+                .json()
+                .await
+                .map_err(|e| Error::StorageList(e.to_string()))?;
+            all_items.extend(page.items.into_iter().map(CosItem::from));
+
+            page_token = page.next_page_token;
+            if page_token.is_none() || reached_max_keys(&mut all_items, self.max_keys) {
+                break;
+            }
+        }
+
+        sort_by_last_modified(&mut all_items);
+        Ok(all_items)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let url = format!(
+            "{}/storage/v1/b/{}/o/{}",
+            self.endpoint,
+            urlencoding::encode(&self.bucket),
+            urlencoding::encode(key)
+        );
+        let res = self
+            .authorize(self.client.delete(&url))
+            .await?
+            .send()
+            .await
+            .map_err(|e| Error::StorageDelete {
+                key: key.to_string(),
+                message: e.to_string(),
+            })?;
+
+        if res.status().is_success() {
+            info!("Successfully deleted: gs://{}/{}", self.bucket, key);
+            Ok(())
+        } else {
+            Err(Error::StorageDelete {
+                key: key.to_string(),
+                message: format!("HTTP code: {}", res.status()),
+            })
+        }
+    }
+}
+
+impl Gcs {
+    pub fn new(config: &GcsConfig) -> Result<Self> {
+        let key = match &config.credentials_file {
+            Some(path) => {
+                let content = std::fs::read_to_string(path).map_err(|e| {
+                    Error::InvalidConfig(format!("Failed to read gcs credentials file: {}", e))
+                })?;
+                let key: ServiceAccountKey = serde_json::from_str(&content)?;
+                Some(key)
+            }
+            // 未配置密钥时匿名访问，用于 fake-gcs-server 等本地测试环境
+            None => None,
+        };
+
+        let chunk_size = config
+            .chunk_size
+            .map(|size| size.div_ceil(CHUNK_ALIGN).max(1) * CHUNK_ALIGN)
+            .unwrap_or(DEFAULT_CHUNK_SIZE);
+
+        Ok(Gcs {
+            client: Client::new(),
+            bucket: config.bucket.clone(),
+            endpoint: config.endpoint.trim_end_matches('/').to_string(),
+            chunk_size,
+            max_keys: config.max_keys,
+            key,
+            token: Mutex::new(None),
+        })
+    }
+
+    async fn authorize(&self, builder: RequestBuilder) -> Result<RequestBuilder> {
+        match self.access_token().await? {
+            Some(token) => Ok(builder.bearer_auth(token)),
+            None => Ok(builder),
+        }
+    }
+
+    /// 用服务账号私钥签发 JWT 换取 OAuth 访问令牌，过期前一分钟刷新
+    async fn access_token(&self) -> Result<Option<String>> {
+        let Some(key) = &self.key else {
+            return Ok(None);
+        };
+
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref()
+            && token.expires_at > Utc::now() + chrono::Duration::seconds(60)
+        {
+            return Ok(Some(token.token.clone()));
+        }
+
+        let token_uri = key.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            iss: &key.client_email,
+            scope: SCOPE,
+            aud: token_uri,
+            iat: now,
+            exp: now + 3600,
+        };
+        let encoding_key = EncodingKey::from_rsa_pem(key.private_key.as_bytes())
+            .map_err(|e| Error::InvalidConfig(format!("Invalid gcs private key: {}", e)))?;
+        let assertion = encode(&Header::new(Algorithm::RS256), &claims, &encoding_key)
+            .map_err(|e| Error::Storage(format!("Failed to sign gcs token request: {}", e)))?;
+
+        let res = self
+            .client
+            .post(token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::Storage(format!("Failed to fetch gcs access token: {}", e)))?;
+        if !res.status().is_success() {
+            return Err(Error::Storage(format!(
+                "Failed to fetch gcs access token, HTTP code: {}",
+                res.status()
+            )));
+        }
+        let token: TokenResponse = res
+            .json()
+            .await
+            .map_err(|e| Error::Storage(format!("Invalid gcs token response: {}", e)))?;
+
+        *cached = Some(AccessToken {
+            token: token.access_token.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(token.expires_in),
+        });
+        Ok(Some(token.access_token))
+    }
+}
+
+/// 308 响应的 `Range: bytes=0-N` 表示已持久化 N + 1 字节，没有 Range 时表示尚未持久化任何数据
+fn persisted_offset(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(RANGE)?.to_str().ok()?;
+    let end = range.strip_prefix("bytes=0-")?.trim().parse::<u64>().ok()?;
+    Some(end + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_response() {
+        let body = r#"{
+            "kind": "storage#objects",
+            "nextPageToken": "token-2",
+            "items": [
+                {
+                    "kind": "storage#object",
+                    "name": "db/orders_20241001_020000.7z",
+                    "size": "2048",
+                    "updated": "2024-10-01T02:00:05.123Z"
+                }
+            ]
+        }"#;
+        let page: GcsListResponse = serde_json::from_str(body).unwrap();
+        assert_eq!(page.next_page_token.as_deref(), Some("token-2"));
+
+        let items: Vec<CosItem> = page.items.into_iter().map(CosItem::from).collect();
+        assert_eq!(items[0].key, "db/orders_20241001_020000.7z");
+        assert_eq!(items[0].size, 2048);
+
+        let last_page: GcsListResponse = serde_json::from_str("{}").unwrap();
+        assert!(last_page.items.is_empty());
+        assert!(last_page.next_page_token.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_stops_at_max_keys() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        // 只应答一次，继续翻页时连接会被拒绝
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+            }
+            let item = |name: &str, day: u32| {
+                format!(
+                    r#"{{"name":"{}","updated":"2024-10-0{}T02:00:00Z","size":"1"}}"#,
+                    name, day
+                )
+            };
+            let body = format!(
+                r#"{{"items":[{},{},{}],"nextPageToken":"page-2"}}"#,
+                item("db/a.7z", 1),
+                item("db/b.7z", 2),
+                item("db/c.7z", 3)
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let storage = Gcs::new(&GcsConfig {
+            bucket: "bucket".to_string(),
+            credentials_file: None,
+            endpoint,
+            chunk_size: None,
+            max_keys: Some(2),
+        })
+        .unwrap();
+        let items = storage.list("db/").await.unwrap();
+
+        let keys: Vec<&str> = items.iter().map(|item| item.key.as_str()).collect();
+        assert_eq!(keys, vec!["db/b.7z", "db/a.7z"]);
+        assert!(server.await.unwrap().contains("maxResults=2"));
+    }
+
+    #[test]
+    fn test_chunk_size_alignment() {
+        let config = GcsConfig {
+            bucket: "bucket".to_string(),
+            credentials_file: None,
+            endpoint: "http://localhost:4443/".to_string(),
+            chunk_size: Some(1000),
+            max_keys: None,
+        };
+        let storage = Gcs::new(&config).unwrap();
+        assert_eq!(storage.chunk_size, CHUNK_ALIGN);
+        assert_eq!(storage.endpoint, "http://localhost:4443");
+    }
+
+    #[test]
+    fn test_persisted_offset() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(persisted_offset(&headers), None);
+        headers.insert(RANGE, "bytes=0-262143".parse().unwrap());
+        assert_eq!(persisted_offset(&headers), Some(262144));
+        headers.insert(RANGE, "bytes=5-10".parse().unwrap());
+        assert_eq!(persisted_offset(&headers), None);
+    }
+}
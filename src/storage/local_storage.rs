@@ -1,10 +1,8 @@
 use super::CosItem;
-use crate::config::AppConfig;
 use crate::error::{Error, Result};
-use crate::storage::Storage;
+use crate::storage::{Storage, sort_by_last_modified, throttle};
 use crate::utils::resolve_path;
 use chrono::{DateTime, Utc};
-use glob::glob;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::fs;
@@ -34,21 +32,90 @@ pub struct LocalStorage {
 
 #[async_trait::async_trait]
 impl Storage for LocalStorage {
-    async fn upload(&self, _file_path: &Path, _cos_path: &str) -> Result<()> {
+    async fn upload(&self, file_path: &Path, cos_path: &str) -> Result<()> {
+        let upload_err = |message: String| Error::StorageUpload {
+            path: file_path.to_path_buf(),
+            message,
+        };
+
+        let file_name = file_path
+            .file_name()
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!("Invalid file path: {}", file_path.display()))
+            })?
+            .to_string_lossy()
+            .to_string();
+
+        let target_dir = self.base_path.join(cos_path.trim_start_matches('/'));
+        fs::create_dir_all(&target_dir)
+            .await
+            .map_err(|e| upload_err(format!("Failed to create target dir: {}", e)))?;
+
+        // 先写入临时文件并落盘，再原子重命名，避免中断时留下不完整的备份
+        let target_path = target_dir.join(&file_name);
+        let temp_path = target_dir.join(format!(".{}.part", file_name));
+        throttle::copy_file(file_path, &temp_path)
+            .await
+            .map_err(|e| upload_err(format!("Failed to copy file: {}", e)))?;
+        fs::File::open(&temp_path)
+            .await
+            .map_err(|e| upload_err(e.to_string()))?
+            .sync_all()
+            .await
+            .map_err(|e| upload_err(format!("Failed to fsync file: {}", e)))?;
+        fs::rename(&temp_path, &target_path)
+            .await
+            .map_err(|e| upload_err(format!("Failed to rename file: {}", e)))?;
+        sync_dir(&target_dir).await;
+
+        info!(
+            "Successfully copied: {} to {}",
+            file_name,
+            target_path.display()
+        );
         Ok(())
     }
 
     async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
-        let pattern = self.base_path.join(key).to_string_lossy().to_string();
-        let mut items = Vec::new();
-
-        let files = glob(&pattern).map_err(|e| Error::StorageList(e.to_string()))?;
+        // 与云存储一致，key 作为前缀匹配，只遍历前缀所在的目录
+        let dir = match key.rfind('/') {
+            Some(idx) => &key[..idx],
+            None => "",
+        };
+        let root = self.base_path.join(dir.trim_start_matches('/'));
+        if !root.is_dir() {
+            return Ok(vec![]);
+        }
 
-        for path in files.flatten() {
-            let metadata = fs::metadata(&path)
+        let mut items = Vec::new();
+        let mut pending = vec![root];
+        while let Some(current) = pending.pop() {
+            let mut entries = fs::read_dir(&current)
                 .await
                 .map_err(|e| Error::StorageList(e.to_string()))?;
-            if metadata.is_file() {
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|e| Error::StorageList(e.to_string()))?
+            {
+                let path = entry.path();
+                let metadata = entry
+                    .metadata()
+                    .await
+                    .map_err(|e| Error::StorageList(e.to_string()))?;
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if !metadata.is_file() || is_partial(&path) {
+                    continue;
+                }
+
+                let relative = self.key_for(&path)?;
+                if !relative.starts_with(key) {
+                    continue;
+                }
+
                 let last_modified: DateTime<Utc> = metadata
                     .modified()
                     .map_err(|e| {
@@ -56,24 +123,17 @@ impl Storage for LocalStorage {
                     })?
                     .into();
 
-                let file_name = path
-                    .file_name()
-                    .ok_or_else(|| {
-                        Error::StorageList(format!("Invalid file path: {}", path.display()))
-                    })?
-                    .to_string_lossy();
-
                 items.push(LocalStorageItem {
-                    key: file_name.to_string(),
+                    key: relative,
                     last_modified,
                     size: metadata.len(),
                 });
             }
         }
 
-        items.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
-
-        Ok(items.into_iter().map(CosItem::from).collect())
+        let mut items: Vec<CosItem> = items.into_iter().map(CosItem::from).collect();
+        sort_by_last_modified(&mut items);
+        Ok(items)
     }
 
     async fn delete(&self, key: &str) -> Result<()> {
@@ -96,19 +156,64 @@ impl Storage for LocalStorage {
 }
 
 impl LocalStorage {
-    pub async fn new(base_path: &str) -> Self {
-        let path = resolve_path(base_path);
-        let backup_path = match path {
-            Ok(p) => p,
-            Err(_) => AppConfig::default().backup_dir,
-        };
-        let _ = fs::create_dir_all(&backup_path).await.map_err(|_| {});
-        LocalStorage {
-            base_path: backup_path,
+    fn key_for(&self, path: &Path) -> Result<String> {
+        let relative = path
+            .strip_prefix(&self.base_path)
+            .map_err(|_| Error::StorageList(format!("Invalid file path: {}", path.display())))?;
+        let segments: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        Ok(segments.join("/"))
+    }
+
+    /// 创建目标目录并规范化路径，目录无法解析或创建时返回配置错误
+    pub async fn new(base_path: &str) -> Result<Self> {
+        let invalid = |e: String| Error::InvalidConfig(format!("local.path {}: {}", base_path, e));
+        let path = resolve_path(base_path).map_err(|e| invalid(e.to_string()))?;
+        fs::create_dir_all(&path)
+            .await
+            .map_err(|e| invalid(e.to_string()))?;
+        let base_path = fs::canonicalize(&path)
+            .await
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(LocalStorage { base_path })
+    }
+
+    /// 备份目录首次运行时可能尚未创建，创建后与目标目录一起规范化再比较，
+    /// 目标目录不能是备份目录本身或其子目录
+    pub async fn check_target(&self, backup_dir: &Path) -> Result<()> {
+        fs::create_dir_all(backup_dir).await?;
+        let backup_dir = fs::canonicalize(backup_dir).await?;
+        if self.base_path.starts_with(&backup_dir) {
+            return Err(Error::InvalidConfig(
+                "local.path must not be app.backup_dir or inside it".into(),
+            ));
         }
+        Ok(())
+    }
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            name.starts_with('.') && name.ends_with(".part")
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+async fn sync_dir(dir: &Path) {
+    // 目录项也需要落盘，否则掉电后重命名可能丢失
+    if let Ok(dir) = fs::File::open(dir).await {
+        let _ = dir.sync_all().await;
     }
 }
 
+#[cfg(not(unix))]
+async fn sync_dir(_dir: &Path) {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,44 +221,88 @@ mod tests {
     use tokio::fs::File;
     use tokio::io::AsyncWriteExt;
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_check_target() {
+        let dir = tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, dir.path().join("link")).unwrap();
+        // 备份目录尚未创建且经过符号链接
+        let backup_dir = dir.path().join("link").join("bk");
+
+        let same = LocalStorage::new(real.join("bk").to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(same.check_target(&backup_dir).await.is_err());
+        let inside = LocalStorage::new(real.join("bk").join("copy").to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(inside.check_target(&backup_dir).await.is_err());
+        let other = LocalStorage::new(real.join("copy").to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(other.check_target(&backup_dir).await.is_ok());
+    }
+
     #[tokio::test]
     async fn test_local_storage_upload() {
-        let temp_dir = tempdir().unwrap();
-        let storage = LocalStorage::new(temp_dir.path().to_str().unwrap()).await;
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let storage = LocalStorage::new(target_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
 
-        let test_file = temp_dir.path().join("test.txt");
+        let test_file = source_dir.path().join("test.txt");
         let mut file = File::create(&test_file).await.unwrap();
         file.write_all(b"test content").await.unwrap();
 
         let result = storage.upload(&test_file, "uploads/").await;
         assert!(result.is_ok());
+
+        let copied = target_dir.path().join("uploads").join("test.txt");
+        assert_eq!(fs::read(&copied).await.unwrap(), b"test content");
+        assert!(test_file.exists());
     }
 
     #[tokio::test]
     async fn test_local_storage_list() {
-        let temp_dir = tempdir().unwrap();
-        let storage = LocalStorage::new(temp_dir.path().to_str().unwrap()).await;
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let storage = LocalStorage::new(target_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
 
-        let test_file = temp_dir.path().join("test.txt");
+        let test_file = source_dir.path().join("test.txt");
         let mut file = File::create(&test_file).await.unwrap();
         file.write_all(b"test content").await.unwrap();
+        storage.upload(&test_file, "db/").await.unwrap();
 
-        let items = storage.list("*.txt").await.unwrap();
-        assert!(!items.is_empty());
-        assert_eq!(items[0].key, "test.txt");
+        let items = storage.list("db/").await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].key, "db/test.txt");
+        assert_eq!(items[0].size, 12);
+
+        let items = storage.list("other/").await.unwrap();
+        assert!(items.is_empty());
     }
 
     #[tokio::test]
     async fn test_local_storage_delete() {
-        let temp_dir = tempdir().unwrap();
-        let storage = LocalStorage::new(temp_dir.path().to_str().unwrap()).await;
+        let source_dir = tempdir().unwrap();
+        let target_dir = tempdir().unwrap();
+        let storage = LocalStorage::new(target_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
 
-        let test_file = temp_dir.path().join("test.txt");
+        let test_file = source_dir.path().join("test.txt");
         let mut file = File::create(&test_file).await.unwrap();
         file.write_all(b"test content").await.unwrap();
+        storage.upload(&test_file, "db/").await.unwrap();
 
-        let result = storage.delete("test.txt").await;
+        let result = storage.delete("db/test.txt").await;
         assert!(result.is_ok());
-        assert!(!test_file.exists());
+        assert!(!target_dir.path().join("db").join("test.txt").exists());
+        assert!(test_file.exists());
     }
 }
@@ -1,6 +1,9 @@
-use crate::error::Result;
+use crate::config::ObjectOptions;
+use crate::error::{Error, Result};
+use crate::i18n::{Msg, t};
 use chrono::{DateTime, Utc};
 use humansize::{DECIMAL, format_size};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow::{self, Borrowed};
 use std::cmp::Ordering;
@@ -8,9 +11,13 @@ use std::path::Path;
 use tabled::Tabled;
 
 pub mod aliyun_oss;
+pub mod gcs;
 pub mod local_storage;
+pub mod retry;
 pub mod s3_compatible;
 pub mod tencent_cos;
+pub mod throttle;
+pub mod webdav;
 
 #[async_trait::async_trait]
 pub trait Storage: Send + Sync {
@@ -49,7 +56,11 @@ impl Ord for CosItem {
 impl Tabled for CosItem {
     const LENGTH: usize = 3;
     fn headers() -> Vec<Cow<'static, str>> {
-        vec![Borrowed("文件路径"), Borrowed("修改时间"), Borrowed("大小")]
+        vec![
+            Borrowed(t(Msg::HeaderKey)),
+            Borrowed(t(Msg::HeaderModified)),
+            Borrowed(t(Msg::HeaderSize)),
+        ]
     }
     fn fields(&self) -> Vec<Cow<'_, str>> {
         let human_size = format_size(self.size, DECIMAL);
@@ -61,3 +72,122 @@ impl Tabled for CosItem {
         ]
     }
 }
+
+/// 设置了 max_keys 时截断到该数量，返回是否已收集够、应停止翻页
+pub(crate) fn reached_max_keys(items: &mut Vec<CosItem>, max_keys: Option<u32>) -> bool {
+    match max_keys {
+        Some(max_keys) if items.len() >= max_keys as usize => {
+            items.truncate(max_keys as usize);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 统一各存储后端 list 的返回顺序：按修改时间从新到旧
+pub fn sort_by_last_modified(items: &mut [CosItem]) {
+    items.sort_by(|a, b| b.cmp(a));
+}
+
+/// 按厂商的请求头前缀(`x-amz`、`x-oss`、`x-cos`)生成上传对象时的附加请求头，
+/// 各家 KMS 密钥 ID 的请求头名称不统一，需要单独传入
+pub fn object_headers(
+    options: &ObjectOptions,
+    prefix: &str,
+    kms_key_id_header: &str,
+) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    let mut insert = |name: &str, value: &str| -> Result<()> {
+        let name = HeaderName::from_bytes(name.to_ascii_lowercase().as_bytes())
+            .map_err(|e| Error::InvalidConfig(format!("Invalid header name {}: {}", name, e)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| Error::InvalidConfig(format!("Invalid header value {}: {}", value, e)))?;
+        headers.insert(name, value);
+        Ok(())
+    };
+
+    if let Some(storage_class) = &options.storage_class {
+        insert(&format!("{}-storage-class", prefix), storage_class)?;
+    }
+    if let Some(encryption) = &options.server_side_encryption {
+        insert(&format!("{}-server-side-encryption", prefix), encryption)?;
+    }
+    if let Some(kms_key_id) = &options.kms_key_id {
+        insert(kms_key_id_header, kms_key_id)?;
+    }
+    for (key, value) in &options.metadata {
+        insert(&format!("{}-meta-{}", prefix, key), value)?;
+    }
+    if !options.tags.is_empty() {
+        let mut tags: Vec<_> = options.tags.iter().collect();
+        tags.sort();
+        let tagging = tags
+            .iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect::<Vec<_>>()
+            .join("&");
+        insert(&format!("{}-tagging", prefix), &tagging)?;
+    }
+
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_by_last_modified() {
+        let item = |key: &str, ts: &str| CosItem {
+            key: key.to_string(),
+            last_modified: DateTime::parse_from_rfc3339(ts)
+                .unwrap()
+                .with_timezone(&Utc),
+            size: 1,
+        };
+        let mut items = vec![
+            item("old", "2024-10-01T00:00:00Z"),
+            item("new", "2024-10-03T00:00:00Z"),
+            item("mid", "2024-10-02T00:00:00Z"),
+        ];
+        sort_by_last_modified(&mut items);
+        let keys: Vec<_> = items.iter().map(|i| i.key.as_str()).collect();
+        assert_eq!(keys, vec!["new", "mid", "old"]);
+    }
+
+    #[test]
+    fn test_object_headers() {
+        let mut options = ObjectOptions {
+            storage_class: Some("STANDARD_IA".to_string()),
+            server_side_encryption: Some("aws:kms".to_string()),
+            kms_key_id: Some("key-1".to_string()),
+            ..Default::default()
+        };
+        options
+            .metadata
+            .insert("Project".to_string(), "orders".to_string());
+        options.tags.insert("env".to_string(), "prod".to_string());
+        options
+            .tags
+            .insert("team".to_string(), "db ops".to_string());
+
+        let headers = object_headers(
+            &options,
+            "x-amz",
+            "x-amz-server-side-encryption-aws-kms-key-id",
+        )
+        .unwrap();
+
+        assert_eq!(headers["x-amz-storage-class"], "STANDARD_IA");
+        assert_eq!(headers["x-amz-server-side-encryption"], "aws:kms");
+        assert_eq!(
+            headers["x-amz-server-side-encryption-aws-kms-key-id"],
+            "key-1"
+        );
+        assert_eq!(headers["x-amz-meta-project"], "orders");
+        assert_eq!(headers["x-amz-tagging"], "env=prod&team=db%20ops");
+
+        let empty = object_headers(&ObjectOptions::default(), "x-cos", "x-cos-kms").unwrap();
+        assert!(empty.is_empty());
+    }
+}
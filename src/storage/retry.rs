@@ -0,0 +1,282 @@
+use super::CosItem;
+use crate::config::{RetryConfig, RetryableError};
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use rand::{RngExt, rng};
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// 给任意存储后端加上超时和指数退避重试。timeout_secs 限制的是整个 upload/list/delete
+/// 操作（包括后端内部的多次请求），不是单个 HTTP 请求
+pub struct RetryStorage {
+    pub inner: Arc<dyn Storage>,
+    pub config: RetryConfig,
+}
+
+#[async_trait::async_trait]
+impl Storage for RetryStorage {
+    async fn upload(&self, file_path: &Path, cos_path: &str) -> Result<()> {
+        let operation = format!("upload {}", file_path.display());
+        // 上传超时说明文件在限定时间内传不完，重试只会从头再传一遍，因此不重试
+        self.run(&operation, false, || self.inner.upload(file_path, cos_path))
+            .await
+    }
+
+    async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
+        let operation = format!("list {}", key);
+        self.run(&operation, true, || self.inner.list(key)).await
+    }
+
+    async fn delete(&self, backup_name: &str) -> Result<()> {
+        let operation = format!("delete {}", backup_name);
+        self.run(&operation, true, || self.inner.delete(backup_name))
+            .await
+    }
+}
+
+impl RetryStorage {
+    pub fn new(inner: Arc<dyn Storage>, config: RetryConfig) -> Self {
+        RetryStorage { inner, config }
+    }
+
+    async fn run<T, F, Fut>(&self, operation: &str, retry_timeout: bool, f: F) -> Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let attempts = self.config.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let result = match self.config.timeout_secs {
+                Some(secs) => tokio::time::timeout(Duration::from_secs(secs), f())
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(Error::Timeout(format!("{} exceeded {}s", operation, secs)))
+                    }),
+                None => f().await,
+            };
+
+            match result {
+                Err(Error::Timeout(message)) if !retry_timeout => {
+                    return Err(Error::Timeout(message));
+                }
+                Err(e) if attempt < attempts && self.is_retryable(&e) => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        "{} failed (attempt {}/{}), retry in {:?}: {}",
+                        operation, attempt, attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn is_retryable(&self, error: &Error) -> bool {
+        let (class, transient) = match error {
+            Error::Timeout(_) => (RetryableError::Timeout, true),
+            Error::Io(e) => (RetryableError::Io, is_transient_io(e)),
+            Error::StorageUpload { message, .. } => (RetryableError::Upload, is_transient(message)),
+            Error::StorageList(message) => (RetryableError::List, is_transient(message)),
+            Error::StorageDelete { message, .. } => (RetryableError::Delete, is_transient(message)),
+            Error::Storage(message) => (RetryableError::Storage, is_transient(message)),
+            // 配置错误、文件不存在、对象锁定等重试也不会成功
+            _ => return false,
+        };
+        transient && self.config.retry_on.contains(&class)
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        backoff(self.config.base_delay_ms, self.config.max_delay_ms, attempt)
+    }
+}
+
+/// 网络错误和超时可能在重试后成功
+const TRANSIENT_MARKERS: &[&str] = &[
+    "timed out",
+    "timeout",
+    "error sending request",
+    "connect",
+    "reset",
+    "broken pipe",
+    "dns",
+    "temporarily unavailable",
+    "unexpected eof",
+];
+
+/// 各存储后端的错误只有文本，带 HTTP 状态码时只重试 5xx、408 和 429，
+/// 403、404、认证失败等永久错误直接返回；否则只重试网络错误和超时
+fn is_transient(message: &str) -> bool {
+    if let Some(status) = http_status(message) {
+        return status >= 500 || status == 408 || status == 429;
+    }
+    let message = message.to_lowercase();
+    TRANSIENT_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// 从 `HTTP code: 503`、`Got HTTP 403 with content ...` 等文本中取出状态码
+fn http_status(message: &str) -> Option<u16> {
+    ["HTTP code: ", "HTTP "].iter().find_map(|prefix| {
+        let start = message.find(prefix)? + prefix.len();
+        let digits = message.get(start..start + 3)?;
+        digits
+            .parse::<u16>()
+            .ok()
+            .filter(|status| (100..600).contains(status))
+    })
+}
+
+fn is_transient_io(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::WouldBlock
+    )
+}
+
+/// 指数退避，并在 [delay/2, delay] 之间加入随机抖动，attempt 从 1 开始
+pub(crate) fn backoff(base_delay_ms: u64, max_delay_ms: u64, attempt: u32) -> Duration {
+    let exp = base_delay_ms.saturating_mul(1u64 << (attempt - 1).min(32));
+    let delay = exp.min(max_delay_ms);
+    let jitter = if delay > 1 {
+        rng().random_range(0..=delay / 2)
+    } else {
+        0
+    };
+    Duration::from_millis(delay - jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct FlakyStorage {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl Storage for FlakyStorage {
+        async fn upload(&self, file_path: &Path, _cos_path: &str) -> Result<()> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if file_path == Path::new("slow.7z") {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            if call < self.failures {
+                Err(Error::StorageUpload {
+                    path: file_path.to_path_buf(),
+                    message: "HTTP code: 503".to_string(),
+                })
+            } else {
+                Ok(())
+            }
+        }
+
+        async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if key == "forbidden/" {
+                return Err(Error::StorageList(
+                    "Got HTTP 403 with content 'AccessDenied'".to_string(),
+                ));
+            }
+            Err(Error::InvalidConfig("bad credentials".to_string()))
+        }
+
+        async fn delete(&self, _backup_name: &str) -> Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        }
+    }
+
+    fn retry_storage(failures: u32) -> (Arc<FlakyStorage>, RetryStorage) {
+        let inner = Arc::new(FlakyStorage {
+            failures,
+            calls: AtomicU32::new(0),
+        });
+        let config = RetryConfig {
+            attempts: 3,
+            base_delay_ms: 1,
+            max_delay_ms: 5,
+            timeout_secs: Some(1),
+            ..Default::default()
+        };
+        (inner.clone(), RetryStorage::new(inner, config))
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let (inner, storage) = retry_storage(2);
+        let result = storage.upload(Path::new("a.7z"), "db/").await;
+        assert!(result.is_ok());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let (inner, storage) = retry_storage(5);
+        let result = storage.upload(Path::new("a.7z"), "db/").await;
+        assert!(matches!(result, Err(Error::StorageUpload { .. })));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error() {
+        let (inner, storage) = retry_storage(0);
+        let result = storage.list("db/").await;
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_storage_error_not_retried() {
+        let (inner, storage) = retry_storage(0);
+        let result = storage.list("forbidden/").await;
+        assert!(matches!(result, Err(Error::StorageList(_))));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient("HTTP code: 503 Service Unavailable"));
+        assert!(is_transient("HTTP code: 429"));
+        assert!(is_transient("error sending request for url (https://x)"));
+        assert!(!is_transient("HTTP code: 403 Forbidden"));
+        assert!(!is_transient("HTTP code: 404 NoSuchBucket"));
+        assert!(!is_transient(
+            "Got HTTP 403 with content 'InvalidAccessKeyId'"
+        ));
+        assert!(!is_transient("SignatureDoesNotMatch"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_is_retried() {
+        let (inner, storage) = retry_storage(0);
+        let result = storage.delete("db/a.7z").await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_upload_timeout_not_retried() {
+        let (inner, storage) = retry_storage(0);
+        let result = storage.upload(Path::new("slow.7z"), "db/").await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,13 +1,24 @@
 use super::CosItem;
-use crate::config::S3OssConfig;
+use crate::config::{ObjectLockConfig, ObjectLockMode, ObjectOptions, S3OssConfig};
 use crate::error::{Error, Result};
-use crate::storage::Storage;
+use crate::storage::{Storage, object_headers, reached_max_keys, sort_by_last_modified, throttle};
+use crate::utils;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use reqwest::header::{CONTENT_LENGTH, HeaderMap, HeaderName, HeaderValue};
+use s3::error::S3Error;
 use s3::{Bucket, Region, creds::Credentials};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tracing::info;
 
+/// 预签名上传链接有效期，只在请求发起时校验
+const PRESIGN_EXPIRY_SECS: u32 = 3600;
+
+static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct S3OssItem {
     #[serde(rename = "Key")]
@@ -32,6 +43,9 @@ impl From<S3OssItem> for CosItem {
 pub struct S3Oss {
     pub bucket: Box<Bucket>,
     pub bucket_name: String,
+    pub object_options: ObjectOptions,
+    pub object_lock: Option<ObjectLockConfig>,
+    pub max_keys: Option<u32>,
 }
 
 #[async_trait::async_trait]
@@ -44,11 +58,7 @@ impl Storage for S3Oss {
             })?
             .to_string_lossy();
 
-        let s3_key = if cos_path.ends_with('/') {
-            format!("{}{}", cos_path, file_name)
-        } else {
-            format!("{}/{}", cos_path, file_name)
-        };
+        let s3_key = utils::object_key(cos_path, &file_name);
 
         // 读取文件内容
         let content = tokio::fs::read(file_path)
@@ -58,17 +68,19 @@ impl Storage for S3Oss {
                 message: format!("Failed to read file: {}", e),
             })?;
 
+        let mut headers = object_headers(
+            &self.object_options,
+            "x-amz",
+            "x-amz-server-side-encryption-aws-kms-key-id",
+        )?;
+        if let Some(lock) = &self.object_lock {
+            headers.extend(object_lock_headers(lock, &content, Utc::now()));
+        }
+
         // 上传到 S3
-        let res = self
-            .bucket
-            .put_object(&s3_key, &content)
-            .await
-            .map_err(|e| Error::StorageUpload {
-                path: file_path.to_path_buf(),
-                message: e.to_string(),
-            })?;
+        let status_code = put_object(&self.bucket, &s3_key, content, headers, file_path).await?;
 
-        if res.status_code() == 200 {
+        if status_code == 200 {
             info!(
                 "Successfully uploaded: {} to s3://{}/{}",
                 file_name, self.bucket_name, s3_key
@@ -77,59 +89,47 @@ impl Storage for S3Oss {
         } else {
             Err(Error::StorageUpload {
                 path: file_path.to_path_buf(),
-                message: format!("HTTP code: {}", res.status_code()),
+                message: format!("HTTP code: {}", status_code),
             })
         }
     }
 
     async fn list(&self, prefix: &str) -> Result<Vec<CosItem>> {
-        let result = self
-            .bucket
-            .list(prefix.to_string(), None)
-            .await
-            .map_err(|e| Error::StorageList(e.to_string()))?;
-
-        let mut all_items = Vec::new();
-        for item in result {
-            let contents = item.contents;
-            let items: Vec<CosItem> = contents
-                .into_iter()
-                .filter_map(|object| {
-                    // Try to parse the last_modified string into DateTime<Utc>
-                    match DateTime::parse_from_rfc3339(&object.last_modified)
-                        .map(|dt| dt.with_timezone(&Utc))
-                    {
-                        Ok(last_modified) => Some(CosItem {
-                            key: object.key.clone(),
-                            last_modified,
-                            size: object.size,
-                        }),
-                        Err(e) => {
-                            info!(
-                                "Failed to parse last_modified: {} ({})",
-                                object.last_modified, e
-                            );
-                            None
-                        }
-                    }
-                })
-                .collect();
-
-            all_items.extend(items);
-        }
-
-        Ok(all_items)
+        list_objects(&self.bucket, prefix, self.max_keys).await
     }
 
     async fn delete(&self, key: &str) -> Result<()> {
-        let res = self
+        // 先检查对象锁定状态，开启版本控制的存储桶中直接删除只会生成删除标记
+        let (head, _) = self
             .bucket
-            .delete_object(key)
+            .head_object(key)
             .await
             .map_err(|e| Error::StorageDelete {
                 key: key.to_string(),
                 message: e.to_string(),
             })?;
+        if let Some(message) = lock_status(
+            head.object_lock_mode.as_deref(),
+            head.object_lock_retain_until_date.as_deref(),
+            head.object_lock_legal_hold_status.as_deref(),
+            Utc::now(),
+        ) {
+            return Err(Error::ObjectLocked {
+                key: key.to_string(),
+                message,
+            });
+        }
+
+        let res = self.bucket.delete_object(key).await.map_err(|e| match e {
+            S3Error::HttpFailWithBody(403, body) if is_lock_denied(&body) => Error::ObjectLocked {
+                key: key.to_string(),
+                message: body,
+            },
+            e => Error::StorageDelete {
+                key: key.to_string(),
+                message: e.to_string(),
+            },
+        })?;
 
         if res.status_code() == 200 || res.status_code() == 204 {
             info!("Successfully deleted: s3://{}/{}", self.bucket_name, key);
@@ -177,6 +177,214 @@ impl S3Oss {
         S3Oss {
             bucket,
             bucket_name: config.bucket.to_string(),
+            object_options: config.object_options.clone(),
+            object_lock: config.object_lock.clone(),
+            max_keys: config.max_keys,
+        }
+    }
+}
+
+/// 上传对象并返回 HTTP 状态码。开启限速时改用预签名 URL 由 reqwest 按配额发送，
+/// 仍是单次 PUT，Object Lock 要求的 Content-MD5 等请求头保持不变
+pub(crate) async fn put_object(
+    bucket: &Bucket,
+    key: &str,
+    content: Vec<u8>,
+    headers: HeaderMap,
+    file_path: &Path,
+) -> Result<u16> {
+    let upload_err = |message: String| Error::StorageUpload {
+        path: file_path.to_path_buf(),
+        message,
+    };
+
+    if !throttle::is_enabled() {
+        let res = bucket
+            .put_object_builder(key, &content)
+            .with_headers(headers)
+            .execute()
+            .await
+            .map_err(|e| upload_err(e.to_string()))?;
+        return Ok(res.status_code());
+    }
+
+    let url = bucket
+        .presign_put(key, PRESIGN_EXPIRY_SECS, Some(headers.clone()), None)
+        .await
+        .map_err(|e| upload_err(e.to_string()))?;
+    let res = HTTP_CLIENT
+        .put(url)
+        .headers(headers)
+        .header(CONTENT_LENGTH, content.len())
+        .body(throttle::body(content))
+        .send()
+        .await
+        .map_err(|e| upload_err(e.to_string()))?;
+    Ok(res.status().as_u16())
+}
+
+/// 分页列出 S3 协议存储桶中的对象，兼容 ListObjectsV2 的 continuation token
+/// 和 ListObjects(v1) 的 marker 两种翻页方式。设置了 max_keys 时最多返回这么多个对象
+pub(crate) async fn list_objects(
+    bucket: &Bucket,
+    prefix: &str,
+    max_keys: Option<u32>,
+) -> Result<Vec<CosItem>> {
+    let mut all_items = Vec::new();
+    let mut continuation_token: Option<String> = None;
+    let mut start_after: Option<String> = None;
+
+    loop {
+        let (page, _) = bucket
+            .list_page(
+                prefix.to_string(),
+                None,
+                continuation_token.take(),
+                start_after.take(),
+                max_keys.map(|n| n as usize),
+            )
+            .await
+            .map_err(|e| Error::StorageList(e.to_string()))?;
+
+        let last_key = page.contents.last().map(|object| object.key.clone());
+        let items = page.contents.into_iter().filter_map(|object| {
+            // Try to parse the last_modified string into DateTime<Utc>
+            match DateTime::parse_from_rfc3339(&object.last_modified)
+                .map(|dt| dt.with_timezone(&Utc))
+            {
+                Ok(last_modified) => Some(CosItem {
+                    key: object.key,
+                    last_modified,
+                    size: object.size,
+                }),
+                Err(e) => {
+                    info!(
+                        "Failed to parse last_modified: {} ({})",
+                        object.last_modified, e
+                    );
+                    None
+                }
+            }
+        });
+        all_items.extend(items);
+
+        if !page.is_truncated || reached_max_keys(&mut all_items, max_keys) {
+            break;
+        }
+        match (page.next_continuation_token, last_key) {
+            (Some(token), _) => continuation_token = Some(token),
+            // 部分兼容实现截断时不返回 NextMarker，此时从最后一个 key 之后继续
+            (None, Some(key)) => start_after = Some(key),
+            (None, None) => break,
         }
     }
+
+    sort_by_last_modified(&mut all_items);
+    Ok(all_items)
+}
+
+/// 生成 Object Lock 上传请求头，S3 要求此类请求必须携带 Content-MD5
+fn object_lock_headers(
+    lock: &ObjectLockConfig,
+    content: &[u8],
+    now: DateTime<Utc>,
+) -> Vec<(HeaderName, HeaderValue)> {
+    let mode = match lock.mode {
+        ObjectLockMode::Governance => "GOVERNANCE",
+        ObjectLockMode::Compliance => "COMPLIANCE",
+    };
+    let retain_until = now + chrono::Duration::days(i64::from(lock.retain_days));
+    let content_md5 = BASE64.encode(md5::compute(content).0);
+
+    let mut headers = vec![
+        ("content-md5", content_md5),
+        ("x-amz-object-lock-mode", mode.to_string()),
+        (
+            "x-amz-object-lock-retain-until-date",
+            retain_until.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ),
+    ];
+    if lock.legal_hold {
+        headers.push(("x-amz-object-lock-legal-hold", "ON".to_string()));
+    }
+
+    headers
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let value = HeaderValue::from_str(&value).ok()?;
+            Some((HeaderName::from_static(name), value))
+        })
+        .collect()
+}
+
+/// 根据 HEAD 返回的锁定信息判断对象当前是否不可删除
+fn lock_status(
+    mode: Option<&str>,
+    retain_until: Option<&str>,
+    legal_hold: Option<&str>,
+    now: DateTime<Utc>,
+) -> Option<String> {
+    if legal_hold.is_some_and(|status| status.eq_ignore_ascii_case("ON")) {
+        return Some("legal hold is ON".to_string());
+    }
+    let until = DateTime::parse_from_rfc3339(retain_until?)
+        .ok()?
+        .with_timezone(&Utc);
+    if until > now {
+        return Some(format!(
+            "{} retention until {}",
+            mode.unwrap_or("unknown"),
+            until.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    None
+}
+
+fn is_lock_denied(body: &str) -> bool {
+    let body = body.to_ascii_lowercase();
+    body.contains("object lock") || body.contains("objectlock") || body.contains("worm")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_lock_headers() {
+        let lock = ObjectLockConfig {
+            mode: ObjectLockMode::Compliance,
+            retain_days: 30,
+            legal_hold: true,
+        };
+        let now = DateTime::parse_from_rfc3339("2024-10-01T02:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let headers = object_lock_headers(&lock, b"hello", now);
+
+        let get = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n.as_str() == name)
+                .map(|(_, v)| v.to_str().unwrap().to_string())
+        };
+        assert_eq!(get("x-amz-object-lock-mode").unwrap(), "COMPLIANCE");
+        assert_eq!(
+            get("x-amz-object-lock-retain-until-date").unwrap(),
+            "2024-10-31T02:00:00Z"
+        );
+        assert_eq!(get("x-amz-object-lock-legal-hold").unwrap(), "ON");
+        assert_eq!(get("content-md5").unwrap(), "XUFAKrxLKna5cZ2REBfFkg==");
+    }
+
+    #[test]
+    fn test_lock_status() {
+        let now = Utc::now();
+        let future = (now + chrono::Duration::days(1)).to_rfc3339();
+        let past = (now - chrono::Duration::days(1)).to_rfc3339();
+
+        assert!(lock_status(Some("GOVERNANCE"), Some(&future), None, now).is_some());
+        assert!(lock_status(Some("GOVERNANCE"), Some(&past), None, now).is_none());
+        assert!(lock_status(None, None, Some("ON"), now).is_some());
+        assert!(lock_status(None, None, Some("OFF"), now).is_none());
+    }
 }
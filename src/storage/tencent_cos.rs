@@ -1,11 +1,14 @@
 use super::CosItem;
-use crate::config::TencentCosConfig;
+use crate::config::{ObjectOptions, TencentCosConfig};
 use crate::error::{Error, Result};
-use crate::storage::Storage;
+use crate::storage::{Storage, object_headers, reached_max_keys, sort_by_last_modified, throttle};
+use crate::utils;
 use chrono::{DateTime, Utc};
-use cos_rust_sdk::{BucketClient, Config, CosClient, ListObjectsV2Options, ObjectClient};
+use cos_rust_sdk::{Auth, BucketClient, Config, CosClient, ListObjectsV2Options, ObjectClient};
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HeaderMap};
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::path::Path;
 use std::time::Duration;
 use tracing::info;
 
@@ -32,17 +35,39 @@ impl From<TencentCosItem> for CosItem {
 #[derive(Debug, Clone)]
 pub struct TencentCos {
     pub client: CosClient,
+    pub auth: Auth,
+    pub bucket_url: String,
+    pub http_client: reqwest::Client,
+    pub object_options: ObjectOptions,
+    pub max_keys: Option<u32>,
 }
 
 #[async_trait::async_trait]
 impl Storage for TencentCos {
     async fn upload(&self, file_path: &Path, cos_path: &str) -> Result<()> {
-        let file_name = file_path.file_name().unwrap().to_string_lossy();
-        let cos_path_full = format!("{}{}", cos_path, file_name);
-        let object_client = ObjectClient::new(self.client.clone());
+        let file_name = file_path
+            .file_name()
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!("Invalid file path: {}", file_path.display()))
+            })?
+            .to_string_lossy();
+        let cos_path_full = utils::object_key(cos_path, &file_name);
 
-        object_client
-            .put_object_from_file(&cos_path_full, &PathBuf::from(file_path), None)
+        // 读取文件内容
+        let content = tokio::fs::read(file_path)
+            .await
+            .map_err(|e| Error::StorageUpload {
+                path: file_path.to_path_buf(),
+                message: format!("Failed to read file: {}", e),
+            })?;
+
+        let headers = object_headers(
+            &self.object_options,
+            "x-cos",
+            "x-cos-server-side-encryption-cos-kms-key-id",
+        )?;
+
+        self.put_object(&cos_path_full, content, headers)
             .await
             .map_err(|e| Error::StorageUpload {
                 path: file_path.to_path_buf(),
@@ -55,45 +80,56 @@ impl Storage for TencentCos {
 
     async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
         let bucket_client = BucketClient::new(self.client.clone());
-        let list_options = ListObjectsV2Options {
-            prefix: Some(key.to_string()),
-            ..Default::default()
-        };
-        let response = bucket_client
-            .list_objects_v2(Some(list_options))
-            .await
-            .map_err(|e| Error::StorageList(e.to_string()))?;
+        let mut result = Vec::new();
+        let mut continuation_token: Option<String> = None;
 
-        if response.contents.is_empty() {
-            return Ok(vec![]);
-        }
+        loop {
+            let list_options = ListObjectsV2Options {
+                prefix: Some(key.to_string()),
+                continuation_token: continuation_token.take(),
+                max_keys: self.max_keys,
+                ..Default::default()
+            };
+            let response = bucket_client
+                .list_objects_v2(Some(list_options))
+                .await
+                .map_err(|e| Error::StorageList(e.to_string()))?;
 
-        let result = response
-            .contents
-            .into_iter()
-            .map(|item| {
-                match DateTime::parse_from_rfc3339(&item.last_modified)
-                    .map(|dt| dt.with_timezone(&Utc))
-                {
-                    Ok(last_modified) => CosItem {
-                        key: item.key,
-                        last_modified,
-                        size: item.size,
-                    },
-                    Err(e) => {
-                        info!(
-                            "Failed to parse last_modified: {} ({})",
-                            item.last_modified, e
-                        );
-                        CosItem {
+            let items =
+                response.contents.into_iter().map(|item| {
+                    match DateTime::parse_from_rfc3339(&item.last_modified)
+                        .map(|dt| dt.with_timezone(&Utc))
+                    {
+                        Ok(last_modified) => CosItem {
                             key: item.key,
-                            last_modified: Utc::now(),
+                            last_modified,
                             size: item.size,
+                        },
+                        Err(e) => {
+                            info!(
+                                "Failed to parse last_modified: {} ({})",
+                                item.last_modified, e
+                            );
+                            CosItem {
+                                key: item.key,
+                                last_modified: Utc::now(),
+                                size: item.size,
+                            }
                         }
                     }
-                }
-            })
-            .collect();
+                });
+            result.extend(items);
+
+            if !response.is_truncated
+                || response.next_continuation_token.is_empty()
+                || reached_max_keys(&mut result, self.max_keys)
+            {
+                break;
+            }
+            continuation_token = Some(response.next_continuation_token);
+        }
+
+        sort_by_last_modified(&mut result);
         Ok(result)
     }
 
@@ -114,6 +150,10 @@ impl Storage for TencentCos {
 
 impl TencentCos {
     pub fn new(config: &TencentCosConfig) -> Self {
+        let object_options = config.object_options.clone();
+        let max_keys = config.max_keys;
+        let auth = Auth::new(config.secret_id.as_str(), config.secret_key.as_str());
+
         // 创建配置
         let config = Config::new(
             &config.secret_id,
@@ -123,9 +163,75 @@ impl TencentCos {
         )
         .with_timeout(Duration::from_secs(30))
         .with_https(true);
+        let bucket_url = config.bucket_url().expect("invalid cos bucket url");
 
         // 创建客户端
         let client = CosClient::new(config).expect("init cos client failed");
-        TencentCos { client }
+        let http_client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(30))
+            .build()
+            .expect("init cos http client failed");
+
+        TencentCos {
+            client,
+            auth,
+            bucket_url,
+            http_client,
+            object_options,
+            max_keys,
+        }
+    }
+
+    /// SDK 的 put_object 不支持自定义请求头，这里复用 SDK 的签名算法直接发起 PUT 请求，
+    /// 并把 x-cos-* 请求头一并纳入签名
+    async fn put_object(&self, key: &str, content: Vec<u8>, headers: HeaderMap) -> Result<()> {
+        let path = format!("/{}", key);
+        let url = format!("{}{}", self.bucket_url, path);
+        let host = reqwest::Url::parse(&self.bucket_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default();
+
+        let mut sign_headers = HashMap::new();
+        sign_headers.insert("host".to_string(), host);
+        for (name, value) in headers.iter() {
+            let value = value
+                .to_str()
+                .map_err(|e| Error::InvalidConfig(e.to_string()))?;
+            sign_headers.insert(name.as_str().to_string(), value.to_string());
+        }
+
+        let now = Utc::now();
+        let authorization = self
+            .auth
+            .sign(
+                "put",
+                &path,
+                &sign_headers,
+                &HashMap::new(),
+                now - chrono::Duration::minutes(5),
+                now + chrono::Duration::hours(1),
+            )
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        let res = self
+            .http_client
+            .put(&url)
+            .headers(headers)
+            .header(AUTHORIZATION, authorization)
+            .header(CONTENT_TYPE, "application/x-7z-compressed")
+            .header(CONTENT_LENGTH, content.len())
+            .body(throttle::body(content))
+            .send()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))?;
+
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            Err(Error::Storage(format!("HTTP code: {} {}", status, body)))
+        }
     }
 }
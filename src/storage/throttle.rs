@@ -0,0 +1,339 @@
+use crate::config::BandwidthConfig;
+use crate::error::{Error, Result};
+use bytes::Bytes;
+use chrono::{Local, NaiveTime};
+use http_body::{Frame, SizeHint};
+use once_cell::sync::OnceCell;
+use std::convert::Infallible;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// 每次申请配额的数据块大小，块越小限速越平滑
+const CHUNK_SIZE: usize = 64 * 1024;
+
+static THROTTLE: OnceCell<Throttle> = OnceCell::new();
+
+/// 全局带宽限制，所有后端的并发传输共享同一个配额
+pub struct Throttle {
+    rate_limit: Option<u64>,
+    windows: Vec<Window>,
+    next_slot: Mutex<Instant>,
+}
+
+struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    rate_limit: Option<u64>,
+}
+
+impl Window {
+    /// 支持跨越午夜的时间段，例如 22:00 - 06:00
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl Throttle {
+    pub fn new(config: &BandwidthConfig) -> Result<Self> {
+        let windows = config
+            .schedule
+            .iter()
+            .map(|window| {
+                Ok(Window {
+                    start: parse_time(&window.start)?,
+                    end: parse_time(&window.end)?,
+                    rate_limit: window.rate_limit,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Throttle {
+            rate_limit: config.rate_limit,
+            windows,
+            next_slot: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// 当前时刻生效的限速，命中的第一个时间段优先于默认值
+    fn rate_at(&self, time: NaiveTime) -> Option<u64> {
+        self.windows
+            .iter()
+            .find(|window| window.contains(time))
+            .map_or(self.rate_limit, |window| window.rate_limit)
+            .filter(|rate| *rate > 0)
+    }
+
+    fn is_limited(&self) -> bool {
+        self.rate_limit.is_some() || self.windows.iter().any(|w| w.rate_limit.is_some())
+    }
+
+    /// 为 bytes 字节预约发送时间，返回可以开始发送的时刻，不限速时返回 None
+    fn reserve(&self, bytes: usize) -> Option<Instant> {
+        let rate = self.rate_at(Local::now().time())?;
+        let mut next_slot = self.next_slot.lock().unwrap();
+        let start = (*next_slot).max(Instant::now());
+        *next_slot = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+        Some(start)
+    }
+
+    /// 等待直到 bytes 字节可以发送
+    pub async fn acquire(&self, bytes: usize) {
+        if let Some(start) = self.reserve(bytes) {
+            tokio::time::sleep_until(start).await;
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M").map_err(|e| {
+        Error::InvalidConfig(format!("invalid bandwidth schedule time {}: {}", value, e))
+    })
+}
+
+/// 根据配置初始化全局限速，重复调用时保留第一次的配置
+pub fn init(config: &BandwidthConfig) -> Result<()> {
+    let throttle = Throttle::new(config)?;
+    let _ = THROTTLE.set(throttle);
+    Ok(())
+}
+
+fn limiter() -> Option<&'static Throttle> {
+    THROTTLE.get().filter(|throttle| throttle.is_limited())
+}
+
+pub fn is_enabled() -> bool {
+    limiter().is_some()
+}
+
+/// 将内存中的数据包装为按配额发送的请求体，未开启限速时直接发送
+pub fn body(content: Vec<u8>) -> reqwest::Body {
+    match limiter() {
+        Some(throttle) => reqwest::Body::wrap(ThrottledBody {
+            content: Bytes::from(content),
+            throttle,
+            sleep: None,
+        }),
+        None => content.into(),
+    }
+}
+
+/// 按 CHUNK_SIZE 分块输出数据，每块发送前先等待预约的时刻
+struct ThrottledBody {
+    content: Bytes,
+    throttle: &'static Throttle,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl http_body::Body for ThrottledBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::result::Result<Frame<Bytes>, Infallible>>> {
+        if self.content.is_empty() {
+            return Poll::Ready(None);
+        }
+        let len = self.content.len().min(CHUNK_SIZE);
+
+        if self.sleep.is_none()
+            && let Some(start) = self.throttle.reserve(len)
+        {
+            self.sleep = Some(Box::pin(tokio::time::sleep_until(start)));
+        }
+        if let Some(sleep) = self.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.sleep = None;
+        }
+
+        let chunk = self.content.split_to(len);
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.content.len() as u64)
+    }
+}
+
+/// 按块读取文件作为请求体，不把整个备份文件读入内存，开启限速时每块发送前等待配额。
+/// len 为发送的字节数，文件在发送过程中变短时请求体返回错误
+pub fn file_body(file: tokio::fs::File, len: u64) -> reqwest::Body {
+    reqwest::Body::wrap(FileBody {
+        file,
+        remaining: len,
+        buffer: vec![0u8; CHUNK_SIZE],
+        throttle: limiter(),
+        reserved: false,
+        sleep: None,
+    })
+}
+
+struct FileBody {
+    file: tokio::fs::File,
+    remaining: u64,
+    buffer: Vec<u8>,
+    throttle: Option<&'static Throttle>,
+    /// 当前数据块是否已经申请过配额，读取未完成时不重复申请
+    reserved: bool,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl http_body::Body for FileBody {
+    type Data = Bytes;
+    type Error = std::io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<std::io::Result<Frame<Bytes>>>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let len = this.remaining.min(CHUNK_SIZE as u64) as usize;
+
+        if !this.reserved {
+            this.reserved = true;
+            if let Some(start) = this.throttle.and_then(|throttle| throttle.reserve(len)) {
+                this.sleep = Some(Box::pin(tokio::time::sleep_until(start)));
+            }
+        }
+        if let Some(sleep) = this.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.sleep = None;
+        }
+
+        let mut buffer = ReadBuf::new(&mut this.buffer[..len]);
+        if let Err(e) = std::task::ready!(Pin::new(&mut this.file).poll_read(cx, &mut buffer)) {
+            return Poll::Ready(Some(Err(e)));
+        }
+        let chunk = buffer.filled();
+        if chunk.is_empty() {
+            return Poll::Ready(Some(Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "file shrank during upload",
+            ))));
+        }
+        this.remaining -= chunk.len() as u64;
+        this.reserved = false;
+        Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(chunk)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+/// 按配额复制文件，用于本地/挂载目录存储
+pub async fn copy_file(from: &Path, to: &Path) -> std::io::Result<u64> {
+    let Some(throttle) = limiter() else {
+        return tokio::fs::copy(from, to).await;
+    };
+
+    let mut reader = tokio::fs::File::open(from).await?;
+    let mut writer = tokio::fs::File::create(to).await?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut copied = 0u64;
+    loop {
+        let n = reader.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        throttle.acquire(n).await;
+        writer.write_all(&buffer[..n]).await?;
+        copied += n as u64;
+    }
+    writer.flush().await?;
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BandwidthWindow;
+
+    fn time(value: &str) -> NaiveTime {
+        parse_time(value).unwrap()
+    }
+
+    #[test]
+    fn test_rate_schedule() {
+        let config = BandwidthConfig {
+            rate_limit: Some(1024),
+            schedule: vec![
+                BandwidthWindow {
+                    start: "22:00".to_string(),
+                    end: "06:00".to_string(),
+                    rate_limit: None,
+                },
+                BandwidthWindow {
+                    start: "12:00".to_string(),
+                    end: "13:00".to_string(),
+                    rate_limit: Some(4096),
+                },
+            ],
+        };
+        let throttle = Throttle::new(&config).unwrap();
+
+        assert_eq!(throttle.rate_at(time("10:00")), Some(1024));
+        assert_eq!(throttle.rate_at(time("12:30")), Some(4096));
+        assert_eq!(throttle.rate_at(time("23:00")), None);
+        assert_eq!(throttle.rate_at(time("05:59")), None);
+        assert_eq!(throttle.rate_at(time("06:00")), Some(1024));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_paces_transfers() {
+        let config = BandwidthConfig {
+            rate_limit: Some(100 * 1024),
+            schedule: vec![],
+        };
+        let throttle = Throttle::new(&config).unwrap();
+
+        let start = std::time::Instant::now();
+        for _ in 0..3 {
+            throttle.acquire(10 * 1024).await;
+        }
+        // 第一块立即发送，后两块各需等待 100ms
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+
+    #[test]
+    fn test_invalid_schedule_time() {
+        let config = BandwidthConfig {
+            rate_limit: None,
+            schedule: vec![BandwidthWindow {
+                start: "25:00".to_string(),
+                end: "06:00".to_string(),
+                rate_limit: Some(1),
+            }],
+        };
+        assert!(matches!(
+            Throttle::new(&config),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
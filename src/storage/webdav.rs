@@ -0,0 +1,710 @@
+use super::CosItem;
+use crate::config::{WebDavAuth, WebDavConfig};
+use crate::error::{Error, Result};
+use crate::storage::{Storage, sort_by_last_modified, throttle};
+use crate::utils;
+use chrono::{DateTime, Utc};
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use rand::{RngExt, rng};
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, HeaderMap, HeaderValue, WWW_AUTHENTICATE};
+use reqwest::{Client, Method, Response, StatusCode};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:getlastmodified/>
+    <d:getcontentlength/>
+    <d:resourcetype/>
+  </d:prop>
+</d:propfind>"#;
+
+/// PROPFIND 响应中的单个资源
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebDavEntry {
+    pub href: String,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub content_length: Option<u64>,
+    pub is_collection: bool,
+}
+
+/// 服务端返回的 Digest 认证挑战
+#[derive(Debug, Clone, Default)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    qop: Option<String>,
+    nc: u32,
+}
+
+/// 请求体。需要按 Digest 挑战重新认证时会再次构造，文件每次重新打开按块发送
+#[derive(Debug, Clone, Copy)]
+enum RequestBody<'a> {
+    Bytes(&'a [u8]),
+    File(&'a Path),
+}
+
+#[derive(Debug)]
+pub struct WebDav {
+    pub client: Client,
+    pub base_url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub auth: WebDavAuth,
+    base_path: String,
+    digest: Mutex<Option<DigestChallenge>>,
+}
+
+#[async_trait::async_trait]
+impl Storage for WebDav {
+    async fn upload(&self, file_path: &Path, cos_path: &str) -> Result<()> {
+        let file_name = file_path
+            .file_name()
+            .ok_or_else(|| {
+                Error::InvalidConfig(format!("Invalid file path: {}", file_path.display()))
+            })?
+            .to_string_lossy();
+
+        let key = utils::object_key(cos_path, &file_name);
+
+        // 逐级创建目录
+        self.ensure_collections(cos_path)
+            .await
+            .map_err(|e| Error::StorageUpload {
+                path: file_path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+        let res = self
+            .send(
+                Method::PUT,
+                &key,
+                HeaderMap::new(),
+                Some(RequestBody::File(file_path)),
+            )
+            .await
+            .map_err(|e| Error::StorageUpload {
+                path: file_path.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+        if res.status().is_success() {
+            info!("Successfully uploaded: {} to {}", file_name, self.url(&key));
+            Ok(())
+        } else {
+            Err(Error::StorageUpload {
+                path: file_path.to_path_buf(),
+                message: format!("HTTP code: {}", res.status()),
+            })
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<CosItem>> {
+        // PROPFIND 只查询前缀所在的目录，Depth: infinity 在多数服务端默认被禁用
+        let dir = match prefix.rfind('/') {
+            Some(idx) => &prefix[..=idx],
+            None => "",
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Depth", HeaderValue::from_static("1"));
+        headers.insert(
+            "Content-Type",
+            HeaderValue::from_static("application/xml; charset=utf-8"),
+        );
+
+        let method = Method::from_bytes(b"PROPFIND").expect("valid method");
+        let res = self
+            .send(
+                method,
+                dir,
+                headers,
+                Some(RequestBody::Bytes(PROPFIND_BODY.as_bytes())),
+            )
+            .await
+            .map_err(|e| Error::StorageList(e.to_string()))?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(vec![]);
+        }
+        if res.status() != StatusCode::MULTI_STATUS && !res.status().is_success() {
+            return Err(Error::StorageList(format!("HTTP code: {}", res.status())));
+        }
+
+        let body = res
+            .text()
+            .await
+            .map_err(|e| Error::StorageList(e.to_string()))?;
+
+        let mut items: Vec<CosItem> = parse_multistatus(&body)?
+            .into_iter()
+            .filter(|entry| !entry.is_collection)
+            .filter_map(|entry| {
+                let key = self.key_from_href(&entry.href)?;
+                if !key.starts_with(prefix) {
+                    return None;
+                }
+                Some(CosItem {
+                    key,
+                    last_modified: entry.last_modified.unwrap_or_else(Utc::now),
+                    size: entry.content_length.unwrap_or(0),
+                })
+            })
+            .collect();
+
+        sort_by_last_modified(&mut items);
+        Ok(items)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let res = self
+            .send(Method::DELETE, key, HeaderMap::new(), None)
+            .await
+            .map_err(|e| Error::StorageDelete {
+                key: key.to_string(),
+                message: e.to_string(),
+            })?;
+
+        if res.status().is_success() {
+            info!("Successfully deleted: {}", self.url(key));
+            Ok(())
+        } else {
+            Err(Error::StorageDelete {
+                key: key.to_string(),
+                message: format!("HTTP code: {}", res.status()),
+            })
+        }
+    }
+}
+
+impl WebDav {
+    pub fn new(config: &WebDavConfig) -> Self {
+        let base_url = config.url.trim_end_matches('/').to_string();
+        let base_path = reqwest::Url::parse(&base_url)
+            .map(|url| url.path().trim_end_matches('/').to_string())
+            .unwrap_or_default();
+
+        WebDav {
+            client: Client::new(),
+            base_url,
+            username: config.username.clone(),
+            password: config.password.clone(),
+            auth: config.auth.clone(),
+            base_path,
+            digest: Mutex::new(None),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        let encoded: Vec<String> = key
+            .split('/')
+            .map(|segment| urlencoding::encode(segment).into_owned())
+            .collect();
+        format!("{}/{}", self.base_url, encoded.join("/"))
+    }
+
+    /// 把 PROPFIND 返回的 href 还原成相对于 `url` 的 key
+    fn key_from_href(&self, href: &str) -> Option<String> {
+        let path = match reqwest::Url::parse(href) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => href.to_string(),
+        };
+        let path = urlencoding::decode(&path).ok()?.into_owned();
+        let base_path = urlencoding::decode(&self.base_path).ok()?.into_owned();
+        let key = path.strip_prefix(&base_path)?.trim_start_matches('/');
+        Some(key.to_string())
+    }
+
+    async fn ensure_collections(&self, cos_path: &str) -> Result<()> {
+        let method = Method::from_bytes(b"MKCOL").expect("valid method");
+        let mut current = String::new();
+        for segment in cos_path.split('/').filter(|s| !s.is_empty()) {
+            current.push_str(segment);
+            current.push('/');
+            let res = self
+                .send(method.clone(), &current, HeaderMap::new(), None)
+                .await?;
+            // 405 表示目录已存在
+            if !res.status().is_success() && res.status() != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(Error::Storage(format!(
+                    "MKCOL {} failed, HTTP code: {}",
+                    current,
+                    res.status()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        headers: HeaderMap,
+        body: Option<RequestBody<'_>>,
+    ) -> Result<Response> {
+        let url = self.url(key);
+
+        // 上传文件时先用无请求体的 HEAD 取得或刷新挑战，避免备份文件因 401 被完整发送两次
+        if matches!(body, Some(RequestBody::File(_))) && self.auth == WebDavAuth::Digest {
+            let probe = self
+                .request(Method::HEAD, &url, HeaderMap::new(), None)
+                .await?;
+            self.update_challenge(&probe);
+            return self.request(method, &url, headers, body).await;
+        }
+
+        let res = self
+            .request(method.clone(), &url, headers.clone(), body)
+            .await?;
+        // 首次请求或 nonce 过期时按服务端挑战重新认证
+        if self.auth == WebDavAuth::Digest && self.update_challenge(&res) {
+            return self.request(method, &url, headers, body).await;
+        }
+
+        Ok(res)
+    }
+
+    /// 响应为 401 且带 Digest 挑战时保存挑战，返回是否需要重新认证
+    fn update_challenge(&self, res: &Response) -> bool {
+        if res.status() != StatusCode::UNAUTHORIZED {
+            return false;
+        }
+        let challenge = res
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_digest_challenge);
+        match challenge {
+            Some(challenge) => {
+                *self.digest.lock().unwrap() = Some(challenge);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        mut headers: HeaderMap,
+        body: Option<RequestBody<'_>>,
+    ) -> Result<Response> {
+        let mut builder = self.client.request(method.clone(), url);
+
+        match self.auth {
+            WebDavAuth::Basic => {
+                if let Some(username) = &self.username {
+                    builder = builder.basic_auth(username, self.password.as_ref());
+                }
+            }
+            WebDavAuth::Digest => {
+                if let Some(value) = self.digest_header(&method, url) {
+                    let value =
+                        HeaderValue::from_str(&value).map_err(|e| Error::Storage(e.to_string()))?;
+                    headers.insert(AUTHORIZATION, value);
+                }
+            }
+        }
+
+        builder = builder.headers(headers);
+        match body {
+            Some(RequestBody::Bytes(content)) => {
+                builder = builder
+                    .header(CONTENT_LENGTH, content.len())
+                    .body(throttle::body(content.to_vec()));
+            }
+            Some(RequestBody::File(path)) => {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .map_err(|e| Error::Storage(format!("Failed to open file: {}", e)))?;
+                let len = file
+                    .metadata()
+                    .await
+                    .map_err(|e| Error::Storage(format!("Failed to read file metadata: {}", e)))?
+                    .len();
+                builder = builder
+                    .header(CONTENT_LENGTH, len)
+                    .body(throttle::file_body(file, len));
+            }
+            None => {}
+        }
+
+        builder
+            .send()
+            .await
+            .map_err(|e| Error::Storage(e.to_string()))
+    }
+
+    fn digest_header(&self, method: &Method, url: &str) -> Option<String> {
+        let mut guard = self.digest.lock().unwrap();
+        let challenge = guard.as_mut()?;
+        challenge.nc += 1;
+
+        let username = self.username.as_deref().unwrap_or_default();
+        let password = self.password.as_deref().unwrap_or_default();
+        let uri = reqwest::Url::parse(url)
+            .map(|u| u.path().to_string())
+            .unwrap_or_else(|_| url.to_string());
+        let cnonce = format!("{:016x}", rng().random::<u64>());
+
+        Some(digest_authorization(
+            challenge,
+            username,
+            password,
+            method.as_str(),
+            &uri,
+            &cnonce,
+        ))
+    }
+}
+
+fn digest_authorization(
+    challenge: &DigestChallenge,
+    username: &str,
+    password: &str,
+    method: &str,
+    uri: &str,
+    cnonce: &str,
+) -> String {
+    let ha1 = format!(
+        "{:x}",
+        md5::compute(format!("{}:{}:{}", username, challenge.realm, password))
+    );
+    let ha2 = format!("{:x}", md5::compute(format!("{}:{}", method, uri)));
+    let nc = format!("{:08x}", challenge.nc);
+
+    let mut header = format!(
+        r#"Digest username="{}", realm="{}", nonce="{}", uri="{}""#,
+        username, challenge.realm, challenge.nonce, uri
+    );
+
+    if challenge.qop.is_some() {
+        let response = format!(
+            "{:x}",
+            md5::compute(format!(
+                "{}:{}:{}:{}:auth:{}",
+                ha1, challenge.nonce, nc, cnonce, ha2
+            ))
+        );
+        header.push_str(&format!(
+            r#", qop=auth, nc={}, cnonce="{}", response="{}""#,
+            nc, cnonce, response
+        ));
+    } else {
+        let response = format!(
+            "{:x}",
+            md5::compute(format!("{}:{}:{}", ha1, challenge.nonce, ha2))
+        );
+        header.push_str(&format!(r#", response="{}""#, response));
+    }
+
+    if let Some(opaque) = &challenge.opaque {
+        header.push_str(&format!(r#", opaque="{}""#, opaque));
+    }
+    header
+}
+
+fn parse_digest_challenge(header: &str) -> Option<DigestChallenge> {
+    let params = header.trim().strip_prefix("Digest")?;
+    let mut challenge = DigestChallenge::default();
+
+    // 按不在引号内的逗号切分参数
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (idx, ch) in params.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                parts.push(&params[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&params[start..]);
+
+    for part in parts {
+        let Some((name, value)) = part.trim().split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "realm" => challenge.realm = value,
+            "nonce" => challenge.nonce = value,
+            "opaque" => challenge.opaque = Some(value),
+            // 只支持 auth，不支持 auth-int
+            "qop" if value.split([',', ' ']).any(|q| q == "auth") => {
+                challenge.qop = Some("auth".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    if challenge.nonce.is_empty() {
+        None
+    } else {
+        Some(challenge)
+    }
+}
+
+/// 解析 PROPFIND 返回的 207 Multi-Status 报文
+pub fn parse_multistatus(xml: &str) -> Result<Vec<WebDavEntry>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut entries = Vec::new();
+    let mut current: Option<WebDavEntry> = None;
+    let mut text = String::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| Error::StorageList(format!("Invalid PROPFIND response: {}", e)))?;
+        match event {
+            Event::Start(e) => {
+                match e.local_name().as_ref() {
+                    b"response" => current = Some(WebDavEntry::default()),
+                    b"collection" => {
+                        if let Some(entry) = current.as_mut() {
+                            entry.is_collection = true;
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Empty(e) => {
+                if e.local_name().as_ref() == b"collection"
+                    && let Some(entry) = current.as_mut()
+                {
+                    entry.is_collection = true;
+                }
+            }
+            Event::Text(e) => {
+                let value = e
+                    .decode()
+                    .map_err(|e| Error::StorageList(format!("Invalid PROPFIND response: {}", e)))?;
+                text.push_str(&value);
+            }
+            Event::GeneralRef(e) => {
+                if let Ok(Some(ch)) = e.resolve_char_ref() {
+                    text.push(ch);
+                } else if let Ok(name) = e.decode()
+                    && let Some(value) = quick_xml::escape::resolve_predefined_entity(&name)
+                {
+                    text.push_str(value);
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                let Some(entry) = current.as_mut() else {
+                    continue;
+                };
+                match name.as_ref() {
+                    b"href" => entry.href = text.trim().to_string(),
+                    b"getlastmodified" => {
+                        entry.last_modified = DateTime::parse_from_rfc2822(text.trim())
+                            .ok()
+                            .map(|dt| dt.with_timezone(&Utc));
+                    }
+                    b"getcontentlength" => entry.content_length = text.trim().parse().ok(),
+                    b"response" => {
+                        if let Some(entry) = current.take() {
+                            entries.push(entry);
+                        }
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    const MULTISTATUS: &str = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+  <d:response>
+    <d:href>/remote.php/dav/files/backup/db/</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getlastmodified>Tue, 01 Oct 2024 02:00:00 GMT</d:getlastmodified>
+        <d:resourcetype><d:collection/></d:resourcetype>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/backup/db/orders_20241001_020000.7z</d:href>
+    <d:propstat>
+      <d:prop>
+        <d:getlastmodified>Tue, 01 Oct 2024 02:00:05 GMT</d:getlastmodified>
+        <d:getcontentlength>1024</d:getcontentlength>
+        <d:resourcetype/>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    fn storage() -> WebDav {
+        WebDav::new(&WebDavConfig {
+            url: "https://cloud.example.com/remote.php/dav/files/backup/".to_string(),
+            username: Some("backup".to_string()),
+            password: Some("secret".to_string()),
+            auth: WebDavAuth::Basic,
+        })
+    }
+
+    #[test]
+    fn test_parse_multistatus() {
+        let entries = parse_multistatus(MULTISTATUS).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_collection);
+        assert!(!entries[1].is_collection);
+        assert_eq!(entries[1].content_length, Some(1024));
+        assert_eq!(
+            entries[1].last_modified.unwrap().to_rfc3339(),
+            "2024-10-01T02:00:05+00:00"
+        );
+    }
+
+    #[test]
+    fn test_key_from_href() {
+        let storage = storage();
+        assert_eq!(
+            storage.key_from_href("/remote.php/dav/files/backup/db/orders%20v2.7z"),
+            Some("db/orders v2.7z".to_string())
+        );
+        assert_eq!(storage.key_from_href("/other/db/orders.7z"), None);
+    }
+
+    #[test]
+    fn test_digest_authorization() {
+        // RFC 2617 3.5 示例
+        let challenge = DigestChallenge {
+            realm: "testrealm@host.com".to_string(),
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093".to_string(),
+            opaque: Some("5ccc069c403ebaf9f0171e9517f40e41".to_string()),
+            qop: Some("auth".to_string()),
+            nc: 1,
+        };
+        let header = digest_authorization(
+            &challenge,
+            "Mufasa",
+            "Circle Of Life",
+            "GET",
+            "/dir/index.html",
+            "0a4f113b",
+        );
+        assert!(header.contains(r#"response="6629fae49393a05397450978507c4ef1""#));
+    }
+
+    /// 依次用 responses 响应每个请求，返回每个请求的请求行、请求头和请求体长度
+    async fn serve(responses: Vec<&'static str>) -> (String, JoinHandle<Vec<(String, Vec<u8>)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/dav", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    head.push_str(&line.to_lowercase());
+                }
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map(|value| value.trim().parse::<usize>().unwrap())
+                    .unwrap_or_default();
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).await.unwrap();
+                requests.push((head, body));
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_digest_upload_sends_body_once() {
+        let challenge = "HTTP/1.1 401 Unauthorized\r\nwww-authenticate: Digest realm=\"dav\", nonce=\"abc\", qop=\"auth\"\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let created = "HTTP/1.1 201 Created\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let (url, server) = serve(vec![challenge, created]).await;
+        let storage = WebDav::new(&WebDavConfig {
+            url,
+            username: Some("backup".to_string()),
+            password: Some("secret".to_string()),
+            auth: WebDavAuth::Digest,
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("orders_20241001_020000.7z");
+        std::fs::write(&file, vec![7u8; 4096]).unwrap();
+
+        storage.upload(&file, "").await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].0.starts_with("head "));
+        assert!(requests[0].1.is_empty());
+        assert!(requests[1].0.starts_with("put "));
+        assert!(requests[1].0.contains("authorization: digest "));
+        assert_eq!(requests[1].1, vec![7u8; 4096]);
+    }
+
+    #[tokio::test]
+    async fn test_basic_upload_and_list_send_body() {
+        let created = "HTTP/1.1 201 Created\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let empty = "HTTP/1.1 207 Multi-Status\r\ncontent-type: application/xml\r\ncontent-length: 31\r\nconnection: close\r\n\r\n<d:multistatus xmlns:d=\"DAV:\"/>";
+        let (url, server) = serve(vec![created, empty]).await;
+        let storage = WebDav::new(&WebDavConfig {
+            url,
+            username: Some("backup".to_string()),
+            password: Some("secret".to_string()),
+            auth: WebDavAuth::Basic,
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("orders_20241001_020000.7z");
+        // 超过一个发送块，确认按块读取的文件被完整发送
+        let content: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        std::fs::write(&file, &content).unwrap();
+
+        storage.upload(&file, "").await.unwrap();
+        storage.list("").await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].0.starts_with("put "));
+        assert!(requests[0].0.contains("authorization: basic "));
+        assert_eq!(requests[0].1.len(), content.len());
+        assert_eq!(requests[0].1, content);
+        assert!(requests[1].0.starts_with("propfind "));
+        assert_eq!(requests[1].1, PROPFIND_BODY.as_bytes());
+    }
+}
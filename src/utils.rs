@@ -1,15 +1,77 @@
+use crate::config::{AfterUploadAction, AfterUploadConfig};
 use crate::error::{Error, Result};
+use crate::i18n::{Msg, t, tf};
 use crate::storage::{CosItem, Storage};
-use chrono::{DateTime, Utc};
-use futures::future::join_all;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use futures::stream::{self, StreamExt};
 use glob::glob;
+use humansize::{DECIMAL, format_size};
+use serde::Serialize;
+use std::borrow::Cow::{self, Borrowed};
 use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
     path::{Path, PathBuf},
     sync::Arc,
+    time::SystemTime,
 };
-use tabled::Table;
-use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tabled::Tabled;
+use tokio::io::AsyncReadExt;
+use tracing::{Instrument, Span, error, info, info_span, warn};
+
+/// 当前主机名，用于在通知中区分来源服务器
+pub fn hostname() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 文本超过 max_bytes 时只保留末尾部分，最新的日志通常最有用。
+/// 结果连同开头的省略标记不超过 max_bytes
+pub fn truncate_head(text: &str, max_bytes: usize) -> String {
+    const MARKER: &str = "...\n";
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    if max_bytes < MARKER.len() {
+        return String::new();
+    }
+    let mut start = text.len() - (max_bytes - MARKER.len());
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("{}{}", MARKER, &text[start..])
+}
+
+/// 上传后对象在存储中的 key，各远端存储后端上传时都用它拼接，
+/// 本地存储按同样的规则拼接目录，只去掉开头的 `/`
+pub fn object_key(cos_path: &str, file_name: &str) -> String {
+    if cos_path.is_empty() || cos_path.ends_with('/') {
+        format!("{}{}", cos_path, file_name)
+    } else {
+        format!("{}/{}", cos_path, file_name)
+    }
+}
+
+/// 分块计算文件的 MD5，避免大备份文件一次性读入内存
+pub async fn file_md5(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut context = md5::Context::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buffer[..n]);
+    }
+    Ok(format!("{:x}", context.finalize()))
+}
 
 pub fn resolve_path(path_str: &str) -> Result<PathBuf> {
     let resolved_path = if path_str.starts_with("~") {
@@ -36,50 +98,279 @@ pub fn is_yesterday_before(date: DateTime<Utc>) -> bool {
     }
 }
 
+/// 单个文件上传失败的原因
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// 批量上传的结果汇总
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UploadReport {
+    pub uploaded: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<UploadFailure>,
+}
+
+impl UploadReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    pub fn total(&self) -> usize {
+        self.uploaded.len() + self.skipped.len() + self.failed.len()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = tf(
+            Msg::UploadSummary,
+            &[
+                ("total", &self.total()),
+                ("uploaded", &self.uploaded.len()),
+                ("skipped", &self.skipped.len()),
+                ("failed", &self.failed.len()),
+            ],
+        );
+        for failure in &self.failed {
+            summary.push_str(&format!("\n{}: {}", failure.path.display(), failure.error));
+        }
+        summary
+    }
+}
+
+/// 以固定并发数上传备份目录中所有 .7z 文件，单个文件失败不会中断其他文件。
+/// skip_uploaded 为 true 时，远端已存在相同 key 且大小一致的文件会被跳过
 pub async fn upload_all_backups(
     backup_dir: &Path,
     storage: Arc<dyn Storage>,
     cos_path: &str,
-) -> Result<()> {
+    concurrency: usize,
+    skip_uploaded: bool,
+) -> Result<UploadReport> {
     let pattern = backup_dir.join("*.7z").to_string_lossy().to_string();
 
     let files = glob(&pattern).map_err(|e| Error::PathResolution(e.to_string()))?;
 
     let files: Vec<PathBuf> = files.into_iter().filter_map(|file| file.ok()).collect();
 
-    let mut tasks: Vec<JoinHandle<Result<()>>> = Vec::with_capacity(files.len());
+    let remote = if skip_uploaded {
+        match storage.list(cos_path).await {
+            Ok(items) => items,
+            Err(e) => {
+                warn!("Failed to list remote backups, uploading all files: {}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    };
 
-    let cos_path = cos_path.to_owned();
+    let mut report = UploadReport::default();
+    let mut pending = Vec::new();
     for file in files {
-        let storage = storage.clone();
-        let cos_path = cos_path.clone();
-        let handle: JoinHandle<Result<()>> =
-            tokio::spawn(async move { storage.upload(&file, &cos_path).await });
-        tasks.push(handle);
+        if is_uploaded(&file, cos_path, &remote) {
+            info!("Skip already uploaded: {}", file.display());
+            report.skipped.push(file);
+        } else {
+            pending.push(file);
+        }
     }
 
-    let _ = join_all(tasks).await;
-    Ok(())
+    let outcomes: Vec<(PathBuf, Result<()>)> = stream::iter(pending)
+        .map(|file| {
+            let storage = storage.clone();
+            async move {
+                let result = storage
+                    .upload(&file, cos_path)
+                    .instrument(upload_span(&file))
+                    .await;
+                (file, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    for (file, result) in outcomes {
+        match result {
+            Ok(()) => report.uploaded.push(file),
+            Err(e) => {
+                error!("Failed to upload {}: {}", file.display(), e);
+                report.failed.push(UploadFailure {
+                    path: file,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+    report.uploaded.sort();
+    report.skipped.sort();
+    report.failed.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(report)
+}
+
+/// 单个文件上传的 span，带文件名和大小
+pub fn upload_span(file: &Path) -> Span {
+    let bytes = std::fs::metadata(file).map(|m| m.len()).unwrap_or_default();
+    info_span!("upload_file", file = %file.display(), bytes)
+}
+
+/// 远端存在相同 key 且大小一致的对象即视为已上传
+fn is_uploaded(file: &Path, cos_path: &str, remote: &[CosItem]) -> bool {
+    let Some(file_name) = file.file_name().map(|name| name.to_string_lossy()) else {
+        return false;
+    };
+    let Ok(metadata) = std::fs::metadata(file) else {
+        return false;
+    };
+    // 本地存储列出的 key 不带开头的 /
+    let key = object_key(cos_path, &file_name);
+    let key = key.trim_start_matches('/');
+    remote
+        .iter()
+        .any(|item| item.key.trim_start_matches('/') == key && item.size == metadata.len())
 }
 
-pub async fn cleanup_old_backups(backup_dir: &Path) -> Result<()> {
+/// 处理已确认上传的本地备份：按修改时间保留最新的 keep_last 个，其余删除或移动。
+/// 上传失败的文件不会被处理，返回被清理的文件列表
+#[tracing::instrument(name = "prune", skip_all, fields(keep_last = config.keep_last))]
+pub async fn prune_uploaded_backups(
+    backup_dir: &Path,
+    report: &UploadReport,
+    config: &AfterUploadConfig,
+) -> Result<Vec<PathBuf>> {
+    let move_to = match config.action {
+        AfterUploadAction::Move => {
+            let move_to = config.move_to.as_deref().ok_or_else(|| {
+                Error::InvalidConfig("after_upload.move_to is required for move action".into())
+            })?;
+            let move_to = resolve_path(move_to)?;
+            tokio::fs::create_dir_all(&move_to).await?;
+            Some(move_to)
+        }
+        AfterUploadAction::Delete => None,
+    };
+
+    let pattern = backup_dir.join("*.7z").to_string_lossy().to_string();
+    let files = glob(&pattern).map_err(|e| Error::PathResolution(e.to_string()))?;
+    let mut files: Vec<(PathBuf, SystemTime)> = files
+        .filter_map(|file| file.ok())
+        .filter_map(|file| {
+            let modified = std::fs::metadata(&file).and_then(|m| m.modified()).ok()?;
+            Some((file, modified))
+        })
+        .collect();
+    files.sort_by_key(|(_, modified)| Reverse(*modified));
+
+    let mut pruned = Vec::new();
+    for (file, _) in files.into_iter().skip(config.keep_last) {
+        if !report.uploaded.contains(&file) && !report.skipped.contains(&file) {
+            continue;
+        }
+        let result = match &move_to {
+            Some(dir) => move_file(&file, &dir.join(file.file_name().unwrap_or_default())).await,
+            None => tokio::fs::remove_file(&file).await,
+        };
+        match result {
+            Ok(()) => {
+                info!("Pruned uploaded backup: {}", file.display());
+                pruned.push(file);
+            }
+            Err(e) => error!("Failed to prune {}: {}", file.display(), e),
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// rename 跨文件系统会失败，此时退化为复制后删除
+async fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+    tokio::fs::copy(from, to).await?;
+    tokio::fs::remove_file(from).await
+}
+
+/// 备份文件名 `<db>_<YYYYMMDD_HHMMSS>.7z` 中解析出的数据库名和备份时间
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupName {
+    pub database: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 解析备份文件名，支持带路径的对象 key，数据库名本身可以包含下划线
+pub fn parse_backup_name(key: &str) -> Option<BackupName> {
+    let file_name = key.rsplit('/').next()?;
+    let stem = file_name.strip_suffix(".7z")?;
+    // 从末尾按下划线切出 YYYYMMDD 和 HHMMSS，数据库名可以包含多字节字符
+    let mut parts = stem.rsplitn(3, '_');
+    let (time, date, database) = (parts.next()?, parts.next()?, parts.next()?);
+    if database.is_empty() || date.len() != 8 || time.len() != 6 {
+        return None;
+    }
+    let created_at = NaiveDateTime::parse_from_str(&format!("{}_{}", date, time), "%Y%m%d_%H%M%S")
+        .ok()?
+        .and_utc();
+    Some(BackupName {
+        database: database.to_string(),
+        created_at,
+    })
+}
+
+/// 按数据库分组，每组保留最新的 min_keep 个，返回其余可删除的项。
+/// 无法识别命名的文件单独成组，因此在 min_keep 大于 0 时永远不会被选中
+pub fn beyond_min_keep<T>(
+    items: Vec<T>,
+    min_keep: usize,
+    key: impl Fn(&T) -> &str,
+    modified: impl Fn(&T) -> DateTime<Utc>,
+) -> Vec<T> {
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for item in items {
+        let group = match parse_backup_name(key(&item)) {
+            Some(name) => name.database,
+            None => key(&item).to_string(),
+        };
+        groups.entry(group).or_default().push(item);
+    }
+
+    groups
+        .into_values()
+        .flat_map(|mut group| {
+            group.sort_by_key(|item| Reverse(modified(item)));
+            group.into_iter().skip(min_keep)
+        })
+        .collect()
+}
+
+/// 远端可删除的备份：超过安全下限且早于昨天的非空对象
+pub fn expired_remote_backups(items: Vec<CosItem>, min_keep: usize) -> Vec<CosItem> {
+    beyond_min_keep(items, min_keep, |item| &item.key, |item| item.last_modified)
+        .into_iter()
+        .filter(|item| is_yesterday_before(item.last_modified) && item.size > 0)
+        .collect()
+}
+
+/// 本地可删除的备份：每个数据库保留最新的 min_keep 个
+pub fn expired_local_backups(backup_dir: &Path, min_keep: usize) -> Result<Vec<PathBuf>> {
     let pattern = backup_dir.join("*.7z").to_string_lossy().to_string();
 
     let files = glob(&pattern).map_err(|e| Error::PathResolution(e.to_string()))?;
 
+    let mut backups = Vec::new();
     for entry in files {
         match entry {
             Ok(path) => {
-                info!("Remove file: {:?}", &path);
-                if let Err(e) = tokio::fs::remove_file(&path).await {
-                    error!(
-                        "Failed to remove old backup {}: {}",
-                        &path.display().to_string(),
-                        e
-                    );
-                } else {
-                    info!("Removed old backup: {}", &path.display().to_string());
-                }
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified())?;
+                let name = path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                backups.push((path, name, DateTime::<Utc>::from(modified)));
             }
             Err(e) => {
                 error!("Error reading file: {}", e);
@@ -87,16 +378,136 @@ pub async fn cleanup_old_backups(backup_dir: &Path) -> Result<()> {
         }
     }
 
-    Ok(())
+    let mut expired: Vec<PathBuf> = beyond_min_keep(backups, min_keep, |b| &b.1, |b| b.2)
+        .into_iter()
+        .map(|(path, _, _)| path)
+        .collect();
+    expired.sort();
+    Ok(expired)
 }
 
-pub fn list_table(files: Vec<CosItem>) -> Result<()> {
-    let table = Table::new(&files).to_string();
-    println!("=== COS 文件列表 ===");
-    println!("{}", table);
+pub async fn cleanup_old_backups(files: &[PathBuf]) -> Result<()> {
+    for path in files {
+        info!("Remove file: {:?}", path);
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            error!(
+                "Failed to remove old backup {}: {}",
+                &path.display().to_string(),
+                e
+            );
+        } else {
+            info!("Removed old backup: {}", &path.display().to_string());
+        }
+    }
+
     Ok(())
 }
 
+/// list 命令的筛选条件，时间按备份文件名中的时间戳比较，无法解析时使用修改时间
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    pub database: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+fn backup_time(item: &CosItem) -> DateTime<Utc> {
+    parse_backup_name(&item.key).map_or(item.last_modified, |name| name.created_at)
+}
+
+/// 按条件筛选备份，结果按备份时间从新到旧，limit 只保留最新的若干个
+pub fn filter_backups(items: Vec<CosItem>, filter: &ListFilter) -> Vec<CosItem> {
+    let mut items: Vec<CosItem> = items
+        .into_iter()
+        .filter(|item| match &filter.database {
+            Some(database) => {
+                parse_backup_name(&item.key).is_some_and(|name| &name.database == database)
+            }
+            None => true,
+        })
+        .filter(|item| filter.since.is_none_or(|since| backup_time(item) >= since))
+        .filter(|item| filter.until.is_none_or(|until| backup_time(item) <= until))
+        .collect();
+    items.sort_by_key(|item| Reverse(backup_time(item)));
+    if let Some(limit) = filter.limit {
+        items.truncate(limit);
+    }
+    items
+}
+
+/// 解析命令行中的时间，支持 `YYYY-MM-DD` 和 `YYYY-MM-DD HH:MM:SS`(UTC)。
+/// 只有日期时 end_of_day 为 true 表示取当天最后一秒，用于 --until
+pub fn parse_datetime(value: &str, end_of_day: bool) -> Result<DateTime<Utc>> {
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(datetime.and_utc());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| Error::CommandExecution(format!("Invalid date {}: {}", value, e)))?;
+    let time = if end_of_day {
+        NaiveTime::from_hms_opt(23, 59, 59)
+    } else {
+        NaiveTime::from_hms_opt(0, 0, 0)
+    };
+    Ok(date.and_time(time.unwrap_or_default()).and_utc())
+}
+
+/// 单个数据库的备份汇总
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BackupSummary {
+    pub database: String,
+    pub count: usize,
+    pub total_size: u64,
+    pub newest: DateTime<Utc>,
+    pub oldest: DateTime<Utc>,
+}
+
+impl Tabled for BackupSummary {
+    const LENGTH: usize = 5;
+    fn headers() -> Vec<Cow<'static, str>> {
+        vec![
+            Borrowed(t(Msg::HeaderDatabase)),
+            Borrowed(t(Msg::HeaderBackupCount)),
+            Borrowed(t(Msg::HeaderTotalSize)),
+            Borrowed(t(Msg::HeaderNewest)),
+            Borrowed(t(Msg::HeaderOldest)),
+        ]
+    }
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            self.database.clone().into(),
+            self.count.to_string().into(),
+            format_size(self.total_size, DECIMAL).into(),
+            self.newest.format("%Y-%m-%d %H:%M").to_string().into(),
+            self.oldest.format("%Y-%m-%d %H:%M").to_string().into(),
+        ]
+    }
+}
+
+/// 按数据库汇总备份数量、大小和时间范围，无法识别命名的文件不参与汇总
+pub fn summarize_backups(items: &[CosItem]) -> Vec<BackupSummary> {
+    let mut summaries: BTreeMap<String, BackupSummary> = BTreeMap::new();
+    for item in items {
+        let Some(name) = parse_backup_name(&item.key) else {
+            continue;
+        };
+        let summary = summaries
+            .entry(name.database.clone())
+            .or_insert_with(|| BackupSummary {
+                database: name.database,
+                count: 0,
+                total_size: 0,
+                newest: name.created_at,
+                oldest: name.created_at,
+            });
+        summary.count += 1;
+        summary.total_size += item.size;
+        summary.newest = summary.newest.max(name.created_at);
+        summary.oldest = summary.oldest.min(name.created_at);
+    }
+    summaries.into_values().collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,4 +543,205 @@ mod tests {
         assert!(resolved.starts_with(&home));
         assert!(resolved.ends_with("testfile"));
     }
+
+    struct FailingStorage;
+
+    #[async_trait::async_trait]
+    impl Storage for FailingStorage {
+        async fn upload(&self, file_path: &Path, _cos_path: &str) -> Result<()> {
+            if file_path.ends_with("bad.7z") {
+                return Err(Error::StorageUpload {
+                    path: file_path.to_path_buf(),
+                    message: "HTTP code: 500".to_string(),
+                });
+            }
+            Ok(())
+        }
+
+        async fn list(&self, key: &str) -> Result<Vec<CosItem>> {
+            Ok(vec![CosItem {
+                key: format!("{}done.7z", key),
+                last_modified: Utc::now(),
+                size: 0,
+            }])
+        }
+
+        async fn delete(&self, _backup_name: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_all_backups_report() {
+        let dir = tempdir().unwrap();
+        for name in ["a.7z", "b.7z", "bad.7z", "done.7z", "c.txt"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        let report = upload_all_backups(dir.path(), Arc::new(FailingStorage), "db/", 2, true)
+            .await
+            .unwrap();
+        assert_eq!(report.uploaded.len(), 2);
+        assert_eq!(report.skipped, vec![dir.path().join("done.7z")]);
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].path.ends_with("bad.7z"));
+        assert!(!report.is_success());
+        assert!(report.summary().contains("HTTP code: 500"));
+
+        let config = AfterUploadConfig {
+            action: AfterUploadAction::Delete,
+            move_to: None,
+            keep_last: 0,
+        };
+        let pruned = prune_uploaded_backups(dir.path(), &report, &config)
+            .await
+            .unwrap();
+        assert_eq!(pruned.len(), 3);
+        assert!(dir.path().join("bad.7z").exists());
+        assert!(!dir.path().join("done.7z").exists());
+    }
+
+    fn item(key: &str, days_ago: i64) -> CosItem {
+        CosItem {
+            key: key.to_string(),
+            last_modified: Utc::now() - chrono::Duration::days(days_ago),
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn test_parse_backup_name() {
+        let name = parse_backup_name("db/order_items_20241001_020000.7z").unwrap();
+        assert_eq!(name.database, "order_items");
+        assert_eq!(
+            name.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2024-10-01 02:00:00"
+        );
+        assert!(parse_backup_name("db/_20241001_020000.7z").is_none());
+        assert!(parse_backup_name("db/orders.7z").is_none());
+        assert!(parse_backup_name("db/orders_20241001_020000.sql").is_none());
+
+        let name = parse_backup_name("db/数据库订单_20241001_020000.7z").unwrap();
+        assert_eq!(name.database, "数据库订单");
+        assert!(parse_backup_name("db/数据库订单_2024100_0200001.7z").is_none());
+        assert!(parse_backup_name("db/数据库订单订单.7z").is_none());
+    }
+
+    #[test]
+    fn test_expired_remote_backups_keeps_floor() {
+        // 连续多天备份失败时，旧备份仍然保留最少数量
+        let items = vec![
+            item("db/orders_20241001_020000.7z", 5),
+            item("db/orders_20240930_020000.7z", 6),
+            item("db/orders_20240929_020000.7z", 7),
+            item("db/orders_20240928_020000.7z", 8),
+            item("db/users_20241001_020000.7z", 5),
+            item("db/unknown.7z", 30),
+        ];
+        let mut expired: Vec<String> = expired_remote_backups(items, 2)
+            .into_iter()
+            .map(|item| item.key)
+            .collect();
+        expired.sort();
+        assert_eq!(
+            expired,
+            vec![
+                "db/orders_20240928_020000.7z".to_string(),
+                "db/orders_20240929_020000.7z".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_and_summarize_backups() {
+        let items = vec![
+            item("db/orders_20241003_020000.7z", 1),
+            item("db/orders_20241002_020000.7z", 2),
+            item("db/orders_20241001_020000.7z", 3),
+            item("db/users_20241002_020000.7z", 2),
+            item("db/config.yaml", 2),
+        ];
+
+        let filter = ListFilter {
+            database: Some("orders".to_string()),
+            since: Some(parse_datetime("2024-10-02", false).unwrap()),
+            until: Some(parse_datetime("2024-10-03", true).unwrap()),
+            limit: Some(1),
+        };
+        let filtered = filter_backups(items.clone(), &filter);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].key, "db/orders_20241003_020000.7z");
+
+        let summaries = summarize_backups(&items);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].database, "orders");
+        assert_eq!(summaries[0].count, 3);
+        assert_eq!(summaries[0].total_size, 3);
+        assert_eq!(
+            summaries[0].newest,
+            parse_datetime("2024-10-03 02:00:00", false).unwrap()
+        );
+        assert_eq!(
+            summaries[0].oldest,
+            parse_datetime("2024-10-01 02:00:00", false).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_md5_and_object_key() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("orders_20241001_020000.7z");
+        std::fs::write(&file_path, b"hello").unwrap();
+
+        let checksum = file_md5(&file_path).await.unwrap();
+        assert_eq!(checksum, "5d41402abc4b2a76b9719d911017c592");
+        assert_eq!(object_key("db/", "a.7z"), "db/a.7z");
+        assert_eq!(object_key("db", "a.7z"), "db/a.7z");
+        assert_eq!(object_key("", "a.7z"), "a.7z");
+    }
+
+    #[test]
+    fn test_truncate_head() {
+        assert_eq!(truncate_head("short", 10), "short");
+        assert_eq!(truncate_head("0123456789", 8), "...\n6789");
+        // 不在字符中间截断，结果不超过上限
+        let text = format!("{}尾", "日志".repeat(10));
+        let truncated = truncate_head(&text, 12);
+        assert!(truncated.len() <= 12);
+        assert!(truncated.ends_with("志尾"));
+        assert_eq!(truncate_head("0123456789", 2), "");
+    }
+
+    #[test]
+    fn test_is_uploaded_matches_full_key() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("orders_20241001_020000.7z");
+        std::fs::write(&file_path, b"hello").unwrap();
+        let item = |key: &str, size: u64| CosItem {
+            key: key.to_string(),
+            last_modified: Utc::now(),
+            size,
+        };
+
+        assert!(is_uploaded(
+            &file_path,
+            "db",
+            &[item("db/orders_20241001_020000.7z", 5)]
+        ));
+        assert!(is_uploaded(
+            &file_path,
+            "/db/",
+            &[item("db/orders_20241001_020000.7z", 5)]
+        ));
+        assert!(!is_uploaded(
+            &file_path,
+            "db/",
+            &[item("db/archive/orders_20241001_020000.7z", 5)]
+        ));
+        assert!(!is_uploaded(
+            &file_path,
+            "db/",
+            &[item("db/orders_20241001_020000.7z", 4)]
+        ));
+    }
 }